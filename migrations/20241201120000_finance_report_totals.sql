@@ -0,0 +1,11 @@
+-- Store the aggregated totals of a finance report next to its range
+ALTER TABLE finance_reports ADD COLUMN invoice_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN quote_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN revenue_before_vat INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN discount_total INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN vat_collected INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN revenue_after_vat INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN paid_total INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN outstanding_total INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN quoted_before_vat INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE finance_reports ADD COLUMN quoted_after_vat INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- Invoice numbering per sending company
+CREATE TABLE IF NOT EXISTS invoice_sequences (
+    id INTEGER PRIMARY KEY NOT NULL,
+    company_id INTEGER NOT NULL UNIQUE,
+    format TEXT NOT NULL DEFAULT '{YYYY}{seq:05}',
+    yearly_reset BOOLEAN NOT NULL DEFAULT TRUE,
+    next_number INTEGER NOT NULL DEFAULT 1,
+    year INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (company_id) REFERENCES companies (id)
+);
@@ -0,0 +1,17 @@
+-- Payments registered against an invoice, partial payments add up to invoices.paid_total
+CREATE TABLE IF NOT EXISTS payments (
+    id INTEGER PRIMARY KEY NOT NULL,
+    invoice_id INTEGER NOT NULL,
+    amount INTEGER NOT NULL,
+    date DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    method TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (invoice_id) REFERENCES invoices (id)
+);
+
+ALTER TABLE invoices ADD COLUMN paid_total INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN written_off_date DATETIME;
+
+-- Invoices marked paid before payments existed count as paid in full
+UPDATE invoices SET paid_total = total_after_vat / 100 WHERE payment_date IS NOT NULL;
@@ -0,0 +1,11 @@
+-- Dunning reminders mailed for overdue invoices, each step is mailed once per invoice
+CREATE TABLE IF NOT EXISTS invoice_reminders (
+    id INTEGER PRIMARY KEY NOT NULL,
+    invoice_id INTEGER NOT NULL,
+    step TEXT NOT NULL,
+    sent_date DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (invoice_id, step),
+    FOREIGN KEY (invoice_id) REFERENCES invoices (id)
+);
@@ -0,0 +1,54 @@
+-- Credit notes reverse (part of) an invoice and are numbered in a sequence of their own
+CREATE TABLE IF NOT EXISTS credit_notes (
+    id INTEGER PRIMARY KEY NOT NULL,
+    invoice_id INTEGER NOT NULL,
+    sender_id INTEGER NOT NULL,
+    recipient_id INTEGER NOT NULL,
+    credit_note_number TEXT NOT NULL,
+    send_date DATETIME DEFAULT CURRENT_TIMESTAMP,
+    remarks TEXT,
+    total_before_vat INTEGER NOT NULL,
+    vat_percentage INTEGER DEFAULT '21',
+    currency TEXT DEFAULT 'EUR' NOT NULL,
+    total_after_vat INTEGER NOT NULL,
+    credit_note_url TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (invoice_id) REFERENCES invoices (id),
+    FOREIGN KEY (sender_id) REFERENCES accounts (id),
+    FOREIGN KEY (recipient_id) REFERENCES accounts (id)
+);
+
+CREATE TABLE IF NOT EXISTS credit_note_lines (
+    id INTEGER PRIMARY KEY NOT NULL,
+    credit_note_id INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (credit_note_id) REFERENCES credit_notes (id)
+);
+
+-- The amount credited after VAT in cents, lowers what is still due on the invoice
+ALTER TABLE invoices ADD COLUMN credited_total INTEGER NOT NULL DEFAULT 0;
+
+-- A sequence is kept per company and document, 'invoice' or 'credit-note'
+CREATE TABLE invoice_sequences_new (
+    id INTEGER PRIMARY KEY NOT NULL,
+    company_id INTEGER NOT NULL,
+    document TEXT NOT NULL DEFAULT 'invoice',
+    format TEXT NOT NULL DEFAULT '{YYYY}{seq:05}',
+    yearly_reset BOOLEAN NOT NULL DEFAULT TRUE,
+    next_number INTEGER NOT NULL DEFAULT 1,
+    year INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (company_id, document),
+    FOREIGN KEY (company_id) REFERENCES companies (id)
+);
+
+INSERT INTO invoice_sequences_new (id, company_id, document, format, yearly_reset, next_number, year, created_at, updated_at)
+SELECT id, company_id, 'invoice', format, yearly_reset, next_number, year, created_at, updated_at FROM invoice_sequences;
+
+DROP TABLE invoice_sequences;
+ALTER TABLE invoice_sequences_new RENAME TO invoice_sequences;
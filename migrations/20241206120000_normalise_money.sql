@@ -0,0 +1,4 @@
+-- Store every total after VAT in cents instead of multiplied by (100 + vat_percentage)
+UPDATE invoices SET total_after_vat = CAST(ROUND(total_after_vat / 100.0) AS INTEGER);
+UPDATE quotes SET total_after_vat = CAST(ROUND(total_after_vat / 100.0) AS INTEGER);
+UPDATE credit_notes SET total_after_vat = CAST(ROUND(total_after_vat / 100.0) AS INTEGER);
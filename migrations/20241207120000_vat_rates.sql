@@ -0,0 +1,6 @@
+-- Tasks can have their own VAT rate, documents without a single rate store a NULL vat_percentage.
+-- Reverse charged documents are taxed at 0% and carry a note with the VAT number of the recipient
+ALTER TABLE tasks ADD COLUMN vat_percentage INTEGER;
+ALTER TABLE quotes ADD COLUMN reverse_charge BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE invoices ADD COLUMN reverse_charge BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE credit_notes ADD COLUMN reverse_charge BOOLEAN NOT NULL DEFAULT FALSE;
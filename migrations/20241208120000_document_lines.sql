@@ -0,0 +1,39 @@
+-- The lines of quotes and invoices are frozen when the document is made, so later changes to
+-- tasks and contracts don't change what a document shows
+CREATE TABLE IF NOT EXISTS quote_lines (
+    id INTEGER PRIMARY KEY NOT NULL,
+    quote_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    quantity REAL NOT NULL,
+    unit TEXT NOT NULL,
+    unit_price INTEGER NOT NULL,
+    total INTEGER NOT NULL,
+    vat_percentage INTEGER NOT NULL,
+    task_id INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (quote_id) REFERENCES quotes (id) ON DELETE CASCADE,
+    FOREIGN KEY (task_id) REFERENCES tasks (id) ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS invoice_lines (
+    id INTEGER PRIMARY KEY NOT NULL,
+    invoice_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    quantity REAL NOT NULL,
+    unit TEXT NOT NULL,
+    unit_price INTEGER NOT NULL,
+    total INTEGER NOT NULL,
+    vat_percentage INTEGER NOT NULL,
+    task_id INTEGER,
+    contract_id INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (invoice_id) REFERENCES invoices (id) ON DELETE CASCADE,
+    FOREIGN KEY (task_id) REFERENCES tasks (id) ON DELETE SET NULL,
+    FOREIGN KEY (contract_id) REFERENCES contracts (id) ON DELETE SET NULL
+);
@@ -0,0 +1,8 @@
+-- A quote is accepted or rejected by the recipient, or expires after expire_date
+ALTER TABLE quotes ADD COLUMN accepted_date DATETIME;
+ALTER TABLE quotes ADD COLUMN accepted_by TEXT;
+ALTER TABLE quotes ADD COLUMN rejected_date DATETIME;
+
+-- expire_date defaulted to the moment the quote was made, give those quotes the default 30 days
+UPDATE quotes SET expire_date = datetime(COALESCE(send_date, created_at), '+30 days')
+WHERE expire_date IS NULL OR expire_date <= created_at;
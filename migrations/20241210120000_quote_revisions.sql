@@ -0,0 +1,13 @@
+-- The quotes of a project are revisions v1, v2, ... and a new revision supersedes the current one
+ALTER TABLE quotes ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE quotes ADD COLUMN superseded_by INTEGER REFERENCES quotes (id);
+
+UPDATE quotes SET revision = (
+    SELECT COUNT(*) FROM quotes AS earlier
+    WHERE earlier.project_id = quotes.project_id AND earlier.id <= quotes.id
+) WHERE project_id IS NOT NULL;
+
+UPDATE quotes SET superseded_by = (
+    SELECT MIN(later.id) FROM quotes AS later
+    WHERE later.project_id = quotes.project_id AND later.id > quotes.id
+) WHERE project_id IS NOT NULL;
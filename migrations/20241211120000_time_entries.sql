@@ -0,0 +1,19 @@
+-- Time worked on a task, logged by hand or with a timer. A running timer has no end_date yet and
+-- an entry is billed once it is on an invoice
+CREATE TABLE IF NOT EXISTS time_entries (
+    id INTEGER PRIMARY KEY NOT NULL,
+    task_id INTEGER NOT NULL,
+    start_date DATETIME NOT NULL,
+    end_date DATETIME,
+    minutes INTEGER NOT NULL DEFAULT 0,
+    note TEXT,
+    invoice_id INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (task_id) REFERENCES tasks (id) ON DELETE CASCADE,
+    FOREIGN KEY (invoice_id) REFERENCES invoices (id) ON DELETE SET NULL
+);
+
+-- Only one timer runs at a time
+CREATE UNIQUE INDEX IF NOT EXISTS time_entries_running ON time_entries ((end_date IS NULL))
+WHERE end_date IS NULL;
@@ -0,0 +1,3 @@
+-- The calendar months a contract invoice covers, the next invoice of the contract starts at period_end
+ALTER TABLE invoices ADD COLUMN period_start DATETIME;
+ALTER TABLE invoices ADD COLUMN period_end DATETIME;
@@ -0,0 +1,21 @@
+-- The notice a client has to give before cancelling, ie. "1m", and the last pause of a contract. A
+-- contract is paused until resumed_date is after paused_date, the time in between isn't invoiced
+ALTER TABLE contracts ADD COLUMN notice_period TEXT;
+ALTER TABLE contracts ADD COLUMN paused_date DATETIME;
+ALTER TABLE contracts ADD COLUMN resumed_date DATETIME;
+
+-- Every change of a contract's monthly rate by indexation. The client is mailed about a change
+-- once and informed_date records when
+CREATE TABLE IF NOT EXISTS contract_rates (
+    id INTEGER PRIMARY KEY NOT NULL,
+    contract_id INTEGER NOT NULL,
+    old_monthly_rate INTEGER NOT NULL,
+    new_monthly_rate INTEGER NOT NULL,
+    percentage REAL NOT NULL,
+    source TEXT NOT NULL,
+    effective_date DATETIME NOT NULL,
+    informed_date DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (contract_id) REFERENCES contracts (id) ON DELETE CASCADE
+);
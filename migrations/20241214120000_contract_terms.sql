@@ -0,0 +1,25 @@
+-- A library of reusable contract clauses, grouped by category
+CREATE TABLE IF NOT EXISTS clauses (
+    id INTEGER PRIMARY KEY NOT NULL,
+    category TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- The terms of a contract in the order they appear in the contract. A term added from the library
+-- copies its clause, so editing a clause doesn't change contracts that were already made
+CREATE TABLE IF NOT EXISTS contract_terms (
+    id INTEGER PRIMARY KEY NOT NULL,
+    contract_id INTEGER NOT NULL,
+    clause_id INTEGER,
+    position INTEGER NOT NULL,
+    category TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (contract_id) REFERENCES contracts (id) ON DELETE CASCADE,
+    FOREIGN KEY (clause_id) REFERENCES clauses (id) ON DELETE SET NULL
+);
@@ -0,0 +1,11 @@
+-- Every status transition of a project, the first change of a project has the default
+-- PENDING_APPROVAL as its old_status
+CREATE TABLE IF NOT EXISTS project_status_changes (
+    id INTEGER PRIMARY KEY NOT NULL,
+    project_id INTEGER NOT NULL,
+    old_status TEXT NOT NULL,
+    new_status TEXT NOT NULL,
+    note TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (project_id) REFERENCES projects (id) ON DELETE CASCADE
+);
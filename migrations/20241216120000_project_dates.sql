@@ -0,0 +1,6 @@
+-- The date a project has to be delivered by. Projects that only have the schema default for their
+-- start and end date, the creation time, get no dates so quotes derive their duration instead
+ALTER TABLE projects ADD COLUMN deadline DATETIME;
+
+UPDATE projects SET start_date = NULL, end_date = NULL
+WHERE start_date = created_at AND end_date = created_at;
@@ -0,0 +1,29 @@
+-- Milestones group the tasks of a project in the order of their position and can be invoiced on
+-- their own as an instalment
+CREATE TABLE IF NOT EXISTS milestones (
+    id INTEGER PRIMARY KEY NOT NULL,
+    project_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    due_date DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (project_id) REFERENCES projects (id) ON DELETE CASCADE
+);
+
+-- A task is ordered by its position among the tasks with the same parent. Only top level tasks
+-- have a milestone, subtasks belong to the milestone of their parent
+ALTER TABLE tasks ADD COLUMN parent_id INTEGER REFERENCES tasks (id) ON DELETE CASCADE;
+ALTER TABLE tasks ADD COLUMN milestone_id INTEGER REFERENCES milestones (id) ON DELETE SET NULL;
+ALTER TABLE tasks ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+UPDATE tasks SET position = (
+    SELECT COUNT(*) FROM tasks AS earlier
+    WHERE earlier.project_id = tasks.project_id
+    AND earlier.id <= tasks.id
+);
+
+-- The milestone a line is grouped under, frozen like the title of the line
+ALTER TABLE quote_lines ADD COLUMN milestone TEXT;
+ALTER TABLE invoice_lines ADD COLUMN milestone TEXT;
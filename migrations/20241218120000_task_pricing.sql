@@ -0,0 +1,8 @@
+-- Tasks are priced by the hour with minute_rate, at a fixed price or per unit. The price is the
+-- fixed price or the price of one unit in cents, quantity_billed counts the units invoiced and is
+-- 1 once a fixed price is invoiced
+ALTER TABLE tasks ADD COLUMN pricing TEXT NOT NULL DEFAULT 'HOURLY';
+ALTER TABLE tasks ADD COLUMN price INTEGER;
+ALTER TABLE tasks ADD COLUMN quantity REAL;
+ALTER TABLE tasks ADD COLUMN unit TEXT;
+ALTER TABLE tasks ADD COLUMN quantity_billed REAL;
@@ -0,0 +1,25 @@
+-- Minute rates of a sender company, for a client account or for a role like "dev" or "design".
+-- A rate card without a company, client or role applies to all of them, the most specific card
+-- that is valid on a date wins
+CREATE TABLE IF NOT EXISTS rate_cards (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    company_id INTEGER,
+    client_id INTEGER,
+    role TEXT,
+    minute_rate INTEGER NOT NULL,
+    valid_from DATETIME,
+    valid_until DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (company_id) REFERENCES companies (id) ON DELETE CASCADE,
+    FOREIGN KEY (client_id) REFERENCES accounts (id) ON DELETE CASCADE
+);
+
+-- The role of a task picks its rate card, the rate card its minute_rate came from is kept
+ALTER TABLE tasks ADD COLUMN role TEXT;
+ALTER TABLE tasks ADD COLUMN rate_card_id INTEGER REFERENCES rate_cards (id) ON DELETE SET NULL;
+
+-- The name of the rate card a line was priced with, frozen like the title of the line
+ALTER TABLE quote_lines ADD COLUMN rate_card TEXT;
+ALTER TABLE invoice_lines ADD COLUMN rate_card TEXT;
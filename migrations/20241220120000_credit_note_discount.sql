@@ -0,0 +1,13 @@
+-- The part of the invoice discount a credit note reverses, negative like its totals. Existing
+-- credit notes get the share of the discount of the amount they credited
+ALTER TABLE credit_notes ADD COLUMN discount INTEGER NOT NULL DEFAULT 0;
+
+UPDATE credit_notes SET discount = COALESCE((
+    SELECT CAST(ROUND(
+        invoices.discount * credit_notes.total_before_vat * 1.0
+        / (invoices.total_before_vat - invoices.discount)
+    ) AS INTEGER)
+    FROM invoices
+    WHERE invoices.id = credit_notes.invoice_id
+    AND invoices.total_before_vat > invoices.discount
+), 0);
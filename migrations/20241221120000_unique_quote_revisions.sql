@@ -0,0 +1,23 @@
+-- A revision is taken once per project. Quotes added without a revision all got revision 1, the
+-- projects that have those are numbered again in the order the quotes were made
+UPDATE quotes SET revision = (
+    SELECT COUNT(*) FROM quotes AS earlier
+    WHERE earlier.project_id = quotes.project_id AND earlier.id <= quotes.id
+) WHERE project_id IN (
+    SELECT project_id FROM quotes
+    WHERE project_id IS NOT NULL
+    GROUP BY project_id, revision
+    HAVING COUNT(*) > 1
+);
+
+UPDATE quotes SET superseded_by = (
+    SELECT MIN(later.id) FROM quotes AS later
+    WHERE later.project_id = quotes.project_id AND later.id > quotes.id
+) WHERE superseded_by IS NULL AND project_id IN (
+    SELECT project_id FROM quotes
+    WHERE project_id IS NOT NULL AND superseded_by IS NULL
+    GROUP BY project_id
+    HAVING COUNT(*) > 1
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS quotes_project_revision ON quotes (project_id, revision);
@@ -0,0 +1,10 @@
+-- Contracts are invoiced and indexed in their own currency, existing contracts take the currency
+-- of their last invoice
+ALTER TABLE contracts ADD COLUMN currency TEXT NOT NULL DEFAULT 'EUR';
+
+UPDATE contracts SET currency = COALESCE((
+    SELECT currency FROM invoices
+    WHERE invoices.contract_id = contracts.id
+    ORDER BY invoices.id DESC
+    LIMIT 1
+), currency);
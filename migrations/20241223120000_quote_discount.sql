@@ -0,0 +1,8 @@
+-- The part of an invoice's discount that comes from its quote. The discount of a quote is spread
+-- over the invoices made from it, existing invoices took the whole discount of their quote
+ALTER TABLE invoices ADD COLUMN quote_discount INTEGER NOT NULL DEFAULT 0;
+
+UPDATE invoices SET quote_discount = COALESCE((
+    SELECT MIN(quotes.discount, COALESCE(invoices.discount, 0)) FROM quotes
+    WHERE quotes.id = invoices.quote_id
+), 0) WHERE quote_id IS NOT NULL;
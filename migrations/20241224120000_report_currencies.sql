@@ -0,0 +1,2 @@
+-- Amounts in different currencies don't add up, a report has a row per currency with its totals
+ALTER TABLE finance_reports ADD COLUMN currency TEXT NOT NULL DEFAULT 'EUR';
@@ -0,0 +1,9 @@
+-- Like the lines of quotes and invoices each credit note line keeps its VAT rate, so the pdf can
+-- be rendered again from the stored lines. Existing lines get the rate of their credit note
+ALTER TABLE credit_note_lines ADD COLUMN vat_percentage INTEGER NOT NULL DEFAULT 21;
+
+UPDATE credit_note_lines SET vat_percentage = COALESCE((
+    SELECT credit_notes.vat_percentage
+    FROM credit_notes
+    WHERE credit_notes.id = credit_note_lines.credit_note_id
+), 21);
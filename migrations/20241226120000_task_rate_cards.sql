@@ -0,0 +1,4 @@
+-- The rate card of a task is picked when a quote or invoice is made, for the company that sends
+-- it. Only a minute rate that was given for the task is kept, the rates frozen from a rate card
+-- are cleared. rate_card_id is only filled in on the tasks a document is priced with
+UPDATE tasks SET minute_rate = NULL, rate_card_id = NULL WHERE rate_card_id IS NOT NULL;
@@ -4,6 +4,7 @@ use anyhow::Result;
 use clap::Parser;
 use sqlx::SqlitePool;
 
+use casual_cli_lib::clapargs::InvoiceMakeArgs;
 use casual_cli_lib::models::*;
 use casual_cli_lib::queries::*;
 use casual_cli_lib::commands::*;
@@ -108,6 +109,16 @@ async fn main() -> Result<()> {
                             log.print(format!("Contract {id} updated"), id, true);
                         }
                     }
+                    AccountCommands::GetInvoiceSequence { company_id, document } => {
+                        log.msg(format!("Getting {} sequence of company {}", document.as_str(), company_id));
+                        let sequence = get_invoice_sequence(&db_pool, *company_id, *document).await?;
+                        log.print(format!("Got invoice sequence of company {company_id}"), sequence, true);
+                    }
+                    AccountCommands::SetInvoiceSequence { company_id, document, sequence } => {
+                        log.msg(format!("Setting {} sequence of company {}", document.as_str(), company_id));
+                        let sequence = set_invoice_sequence(&db_pool, *company_id, *document, sequence).await?;
+                        log.print(format!("Invoice sequence of company {company_id} set"), sequence, true);
+                    }
                     AccountCommands::Remove { id } => {
                         log.msg(format!("Removing account {}", id));
                         if sqlx::query!(r#"DELETE FROM accounts WHERE id = ?"#, id)
@@ -147,11 +158,172 @@ async fn main() -> Result<()> {
                             log.print(format!("Contract {} removed", id), id, true);
                         }
                     }
+                    AccountCommands::RenewContract { id, period } => {
+                        log.msg(format!("Renewing contract {}", id));
+                        let end_date = renew_contract(&db_pool, *id, *period).await?;
+                        let contract = get_contract(&db_pool, *id).await?;
+                        log.print(format!("Contract {id} renewed until {}", end_date.format("%d-%m-%Y")), contract, true);
+                    }
+                    AccountCommands::CancelContract { id, effective_date } => {
+                        log.msg(format!("Cancelling contract {}", id));
+                        let cancel_date = cancel_contract(&db_pool, *id, *effective_date).await?;
+                        let contract = get_contract(&db_pool, *id).await?;
+                        log.print(format!("Contract {id} cancelled per {}", cancel_date.format("%d-%m-%Y")), contract, true);
+                    }
+                    AccountCommands::PauseContract { id } => {
+                        log.msg(format!("Pausing contract {}", id));
+                        pause_contract(&db_pool, *id).await?;
+                        let contract = get_contract(&db_pool, *id).await?;
+                        log.print(format!("Contract {id} paused"), contract, true);
+                    }
+                    AccountCommands::ResumeContract { id } => {
+                        log.msg(format!("Resuming contract {}", id));
+                        resume_contract(&db_pool, *id).await?;
+                        let contract = get_contract(&db_pool, *id).await?;
+                        log.print(format!("Contract {id} resumed"), contract, true);
+                    }
+                    AccountCommands::IndexContract { id, indexation } => {
+                        log.msg(format!("Indexing contract {}", id));
+                        let (percentage, source) = match (indexation.percentage, indexation.cpi) {
+                            (_, Some(cpi)) => (cpi, IndexationSource::Cpi),
+                            (percentage, None) => (percentage.unwrap_or(0.0), IndexationSource::Percentage),
+                        };
+                        let rate = index_contract(&db_pool, *id, percentage, source).await?;
+                        log.print(format!("Contract {id} indexed, the client is informed by the mailer"), rate, true);
+                    }
+                    AccountCommands::ListContractRates { contract_id } => {
+                        log.msg(format!("Listing rate changes of contract {}", contract_id));
+                        log.msg("------------------------------------".to_string());
+                        let rates = get_contract_rates(&db_pool, *contract_id).await?;
+
+                        log_list!(log, mode, rates);
+                    }
+                    AccountCommands::GetClause { id } => {
+                        log.msg(format!("Getting clause with id {}", id));
+                        let clause = get_clause(&db_pool, *id).await?;
+                        log.print(format!("Got clause {id}"), clause, true);
+                    }
+                    AccountCommands::AddClause { clause } => {
+                        log.msg(format!("Adding clause {}", clause.title));
+                        let id = add_clause(&db_pool, clause).await?;
+                        log.print("Clause added with id".to_string(), id, true);
+                    }
+                    AccountCommands::UpdateClause { id, clause } => {
+                        log.msg(format!("Updating clause {}", id));
+                        let updated = update_clause(&db_pool, *id, clause).await?;
+                        if updated == 0 {
+                            log.print(format!("Clause {} not found", id), -1, true);
+                        } else {
+                            log.print(format!("Clause {id} updated"), id, true);
+                        }
+                    }
+                    AccountCommands::RemoveClause { id } => {
+                        log.msg(format!("Removing clause {}", id));
+                        if sqlx::query!(r#"DELETE FROM clauses WHERE id = ?"#, id)
+                            .execute(&db_pool)
+                            .await?
+                            .rows_affected()
+                            == 0
+                        {
+                            log.print(format!("Clause {} not found", id), -1, true);
+                        } else {
+                            log.print(format!("Clause {} removed", id), id, true);
+                        }
+                    }
+                    AccountCommands::ListClauses { category } => {
+                        log.msg("Listing all clauses".to_string());
+                        log.msg("-------------------".to_string());
+                        let clauses = get_clauses(&db_pool, category.as_deref()).await?;
+
+                        log_list!(log, mode, clauses);
+                    }
+                    AccountCommands::GetRateCard { id } => {
+                        log.msg(format!("Getting rate card with id {}", id));
+                        let rate_card = get_rate_card(&db_pool, *id).await?;
+                        log.print(format!("Got rate card {id}"), rate_card, true);
+                    }
+                    AccountCommands::AddRateCard { rate_card } => {
+                        log.msg(format!("Adding rate card {}", rate_card.name));
+                        let id = add_rate_card(&db_pool, rate_card).await?;
+                        log.print("Rate card added with id".to_string(), id, true);
+                    }
+                    AccountCommands::UpdateRateCard { id, rate_card } => {
+                        log.msg(format!("Updating rate card {}", id));
+                        let updated = update_rate_card(&db_pool, *id, rate_card).await?;
+                        if updated == 0 {
+                            log.print(format!("Rate card {} not found", id), -1, true);
+                        } else {
+                            log.print(format!("Rate card {id} updated"), id, true);
+                        }
+                    }
+                    AccountCommands::RemoveRateCard { id } => {
+                        log.msg(format!("Removing rate card {}", id));
+                        if sqlx::query!(r#"DELETE FROM rate_cards WHERE id = ?"#, id)
+                            .execute(&db_pool)
+                            .await?
+                            .rows_affected()
+                            == 0
+                        {
+                            log.print(format!("Rate card {} not found", id), -1, true);
+                        } else {
+                            log.print(format!("Rate card {} removed", id), id, true);
+                        }
+                    }
+                    AccountCommands::ListRateCards { company_id, client_id, role } => {
+                        log.msg("Listing all rate cards".to_string());
+                        log.msg("----------------------".to_string());
+                        let rate_cards =
+                            get_rate_cards(&db_pool, *company_id, *client_id, role.as_deref()).await?;
+
+                        log_list!(log, mode, rate_cards);
+                    }
+                    AccountCommands::AddContractTerm { contract_id, term } => {
+                        log.msg(format!("Adding term to contract {}", contract_id));
+                        let id = add_contract_term(&db_pool, *contract_id, term).await?;
+                        log.print("Contract term added with id".to_string(), id, true);
+                    }
+                    AccountCommands::UpdateContractTerm { id, term } => {
+                        log.msg(format!("Updating contract term {}", id));
+                        let updated = update_contract_term(&db_pool, *id, term).await?;
+                        if updated == 0 {
+                            log.print(format!("Contract term {} not found", id), -1, true);
+                        } else {
+                            log.print(format!("Contract term {id} updated"), id, true);
+                        }
+                    }
+                    AccountCommands::RemoveContractTerm { id } => {
+                        log.msg(format!("Removing contract term {}", id));
+                        if remove_contract_term(&db_pool, *id).await? == 0 {
+                            log.print(format!("Contract term {} not found", id), -1, true);
+                        } else {
+                            log.print(format!("Contract term {} removed", id), id, true);
+                        }
+                    }
+                    AccountCommands::ListContractTerms { contract_id } => {
+                        log.msg(format!("Listing terms of contract {}", contract_id));
+                        log.msg("------------------------------".to_string());
+                        let terms = get_contract_terms(&db_pool, *contract_id).await?;
+
+                        log_list!(log, mode, terms);
+                    }
+                    AccountCommands::MakeContract { id } => {
+                        log.msg(format!("Making contract {}", id));
+                        let contract_url = make_contract(&db_pool, *id).await?;
+                        if mode == PrintMode::Json {
+                            log.print("".to_string(), Jchar::from('['), false);
+                        }
+                        log.print(
+                            "Contract made, url:".to_string(),
+                            contract_url,
+                            mode != PrintMode::Json,
+                        );
+                        if mode == PrintMode::Json {
+                            log.print("".to_string(), Jchar::from(']'), false);
+                        }
+                    }
                     AccountCommands::GetAddress { id } => {
                         log.msg(format!("Getting address with id {}", id));
-                        let address = sqlx::query_as!(Address, "SELECT * FROM address WHERE id = ?", id)
-                            .fetch_one(&db_pool)
-                            .await?;
+                        let address = get_address(&db_pool, *id).await?;
                         log.print(format!("Got address {id}"), address, true);
                     }
                     AccountCommands::RemoveAddress { id } => {
@@ -280,6 +452,32 @@ async fn main() -> Result<()> {
                     log.print(format!("Task {id} completed"), id, true);
                 }
             }
+            Some(ProjectCommands::StartTimer { task_id, note }) => {
+                log.msg(format!("Starting timer on task {}", task_id));
+                let time_entry = start_timer(&db_pool, *task_id, note.as_deref()).await?;
+                log.print(format!("Timer started on task {task_id}"), time_entry, true);
+            }
+            Some(ProjectCommands::StopTimer) => {
+                log.msg("Stopping timer".to_string());
+                let time_entry = stop_timer(&db_pool).await?;
+                log.print(
+                    format!("Timer of task {} stopped", time_entry.task_id),
+                    time_entry,
+                    true,
+                );
+            }
+            Some(ProjectCommands::LogTime { task_id, args }) => {
+                log.msg(format!("Logging {} on task {}", args.duration, task_id));
+                let time_entry = log_time(&db_pool, *task_id, args).await?;
+                log.print(format!("Time logged on task {task_id}"), time_entry, true);
+            }
+            Some(ProjectCommands::ListTimeEntries { task_id }) => {
+                log.msg("Listing time entries".to_string());
+                log.msg("--------------------".to_string());
+
+                let time_entries = get_time_entries(&db_pool, *task_id).await?;
+                log_list!(log, mode, time_entries);
+            }
             Some(ProjectCommands::Remove { id }) => {
                 log.msg(format!("Removing project {}", id));
                 if sqlx::query!(r#"DELETE FROM projects WHERE id = ?"#, id)
@@ -295,17 +493,39 @@ async fn main() -> Result<()> {
             }
             Some(ProjectCommands::RemoveTask { id }) => {
                 log.msg(format!("Removing task {}", id));
-                if sqlx::query!(r#"DELETE FROM tasks WHERE id = ?"#, id)
-                    .execute(&db_pool)
-                    .await?
-                    .rows_affected()
-                    == 0
-                {
+                if remove_project_task(&db_pool, *id).await? == 0 {
                     log.print(format!("Task {} not found", id), -1, true);
                 } else {
                     log.print(format!("Task {} removed", id), id, true);
                 }
             }
+            Some(ProjectCommands::GetMilestone { id }) => {
+                log.msg(format!("Getting milestone with id {}", id));
+                let milestone = get_milestone(&db_pool, *id).await?;
+                log.print(format!("Got milestone {id}"), milestone, true);
+            }
+            Some(ProjectCommands::AddMilestone { milestone }) => {
+                log.msg(format!("Adding milestone {}", milestone.title));
+                let id = add_milestone(&db_pool, milestone).await?;
+                log.print("Milestone added with id".to_string(), id, true);
+            }
+            Some(ProjectCommands::UpdateMilestone { id, milestone }) => {
+                log.msg(format!("Updating milestone {}", id));
+                let updated = update_milestone(&db_pool, *id, milestone).await?;
+                if updated == 0 {
+                    log.print(format!("Milestone {} not found", id), -1, true);
+                } else {
+                    log.print(format!("Milestone {id} updated"), id, true);
+                }
+            }
+            Some(ProjectCommands::RemoveMilestone { id }) => {
+                log.msg(format!("Removing milestone {}", id));
+                if remove_milestone(&db_pool, *id).await? == 0 {
+                    log.print(format!("Milestone {} not found", id), -1, true);
+                } else {
+                    log.print(format!("Milestone {} removed", id), id, true);
+                }
+            }
             Some(ProjectCommands::RemoveQuote { id }) => {
                 log.msg(format!("Removing quote {}", id));
                 if sqlx::query!(r#"DELETE FROM quotes WHERE id = ?"#, id)
@@ -332,7 +552,14 @@ async fn main() -> Result<()> {
                     log.print(format!("Invoice {} removed", id), id, true);
                 }
             }
-            Some(ProjectCommands::GetQuote { id }) => {
+            Some(ProjectCommands::GetQuote { id, lines: true }) => {
+                log.msg(format!("Listing lines of quote {}", id));
+                log.msg("--------------------".to_string());
+
+                let lines = get_quote_lines(&db_pool, *id).await?;
+                log_list!(log, mode, lines);
+            }
+            Some(ProjectCommands::GetQuote { id, lines: false }) => {
                 log.msg(format!("Getting quote with id {}", id));
                 let quote = get_quote(&db_pool, *id).await?;
                 log.print(format!("Got quote {id}"), quote, true);
@@ -352,6 +579,63 @@ async fn main() -> Result<()> {
                     log.print("".to_string(), Jchar::from(']'), false);
                 }
             }
+            Some(ProjectCommands::RenderQuote { id }) => {
+                log.msg(format!("Rendering quote {}", id));
+                let quote_url = render_quote(&db_pool, *id).await?;
+                if &mode == &PrintMode::Json {
+                    log.print("".to_string(), Jchar::from('['), false);
+                }
+                log.print(
+                    "Quote rendered, url:".to_string(),
+                    quote_url,
+                    &mode != &PrintMode::Json,
+                );
+                if &mode == &PrintMode::Json {
+                    log.print("".to_string(), Jchar::from(']'), false);
+                }
+            }
+            Some(ProjectCommands::AcceptQuote { id, args }) => {
+                log.msg(format!("Accepting quote {}", id));
+                let quote = accept_quote(&db_pool, *id, args.accepted_by.as_deref()).await?;
+
+                if args.invoice {
+                    let invoice_make_args = InvoiceMakeArgs {
+                        quote_id: Some(quote.id),
+                        project_id: None,
+                        contract_id: None,
+                        sender_id: None,
+                        remarks: None,
+                        discount: None,
+                        vat_percentage: None,
+                        time_entries: false,
+                        milestone_id: None,
+                        dry_run: false,
+                    };
+                    let invoice_url = make_invoice(&db_pool, &invoice_make_args).await?;
+                    log.msg(format!("Invoice made, url: {}", invoice_url));
+                } else {
+                    log.msg(format!(
+                        "Make the invoice with: project make-invoice --quote-id {}",
+                        quote.id
+                    ));
+                }
+
+                log.print(format!("Quote {id} accepted"), quote, true);
+            }
+            Some(ProjectCommands::RejectQuote { id }) => {
+                log.msg(format!("Rejecting quote {}", id));
+                let quote = reject_quote(&db_pool, *id).await?;
+                log.print(format!("Quote {id} rejected"), quote, true);
+            }
+            Some(ProjectCommands::DiffQuote { id, against }) => {
+                log.msg(format!("Comparing quote {}", id));
+                let diff = diff_quote(&db_pool, *id, *against).await?;
+                log.print(
+                    format!("Quote v{} -> v{}", diff.old_revision, diff.new_revision),
+                    diff,
+                    true,
+                );
+            }
             Some(ProjectCommands::UpdateQuote { id, args }) => {
                 log.msg(format!("Updating quote {}", id));
                 let updated = update_quote(&db_pool, *id, args).await?;
@@ -361,7 +645,14 @@ async fn main() -> Result<()> {
                     log.print(format!("Quote {id} updated"), id, true);
                 }
             }
-            Some(ProjectCommands::GetInvoice { id }) => {
+            Some(ProjectCommands::GetInvoice { id, lines: true }) => {
+                log.msg(format!("Listing lines of invoice {}", id));
+                log.msg("--------------------".to_string());
+
+                let lines = get_invoice_lines(&db_pool, *id).await?;
+                log_list!(log, mode, lines);
+            }
+            Some(ProjectCommands::GetInvoice { id, lines: false }) => {
                 log.msg(format!("Getting invoice with id {}", id));
                 let invoice = get_invoice(&db_pool, *id).await?;
                 log.print(format!("Got invoice {id}"), invoice, true);
@@ -391,9 +682,33 @@ async fn main() -> Result<()> {
                     return Ok(());
                 }
 
+                if args.dry_run {
+                    log.msg("Lines that would be billed".to_string());
+                    log.msg("--------------------".to_string());
+
+                    let lines = preview_invoice(&db_pool, args).await?;
+                    log_list!(log, mode, lines);
+                    return Ok(());
+                }
+
                 let invoice_url = make_invoice(&db_pool, args).await?;
                 log.print("Invoice made, url:".to_string(), invoice_url, true);
             }
+            Some(ProjectCommands::RenderInvoice { id }) => {
+                log.msg(format!("Rendering invoice {}", id));
+                let invoice_url = render_invoice(&db_pool, *id).await?;
+                if &mode == &PrintMode::Json {
+                    log.print("".to_string(), Jchar::from('['), false);
+                }
+                log.print(
+                    "Invoice rendered, url:".to_string(),
+                    invoice_url,
+                    &mode != &PrintMode::Json,
+                );
+                if &mode == &PrintMode::Json {
+                    log.print("".to_string(), Jchar::from(']'), false);
+                }
+            }
             Some(ProjectCommands::UpdateInvoice { id, args }) => {
                 log.msg(format!("Updating invoice {}", id));
                 let updated = update_invoice(&db_pool, *id, args).await?;
@@ -403,31 +718,56 @@ async fn main() -> Result<()> {
                     log.print(format!("Invoice {id} updated"), id, true);
                 }
             }
-            Some(ProjectCommands::List) => {
+            Some(ProjectCommands::List { status }) => {
                 log.msg("Listing all projects".to_string());
                 log.msg("--------------------".to_string());
-                let projects = sqlx::query_as!(Project, "SELECT * FROM projects")
+                let status = status.map(|status| status.as_str());
+                let projects = sqlx::query_as!(
+                    Project,
+                    "SELECT * FROM projects WHERE ($1 IS NULL OR status = $1)",
+                    status
+                )
                     .fetch_all(&db_pool)
                     .await?;
 
                 log_list!(log, mode, projects);
             }
+            Some(ProjectCommands::SetStatus { id, status, note }) => {
+                log.msg(format!("Setting status of project {}", id));
+                let project = set_project_status(&db_pool, *id, *status, note.as_deref()).await?;
+                log.print(format!("Project {id} status set"), project, true);
+            }
+            Some(ProjectCommands::ListStatusChanges { id }) => {
+                log.msg(format!("Listing status changes of project {}", id));
+                log.msg("--------------------".to_string());
+                let changes = get_project_status_changes(&db_pool, *id).await?;
+
+                log_list!(log, mode, changes);
+            }
             Some(ProjectCommands::ListTasks { id }) => {
                 log.msg(format!("Listing all tasks for project {}", id));
                 log.msg("---------------------------------".to_string());
-                let tasks =
-                    sqlx::query_as!(ProjectTask, "SELECT * FROM tasks WHERE project_id = ?", id)
-                        .fetch_all(&db_pool)
-                        .await?;
+                let tasks = get_ordered_project_tasks(&db_pool, *id)
+                    .await?
+                    .into_iter()
+                    .map(|ordered_task| ordered_task.task)
+                    .collect::<Vec<_>>();
 
                 log_list!(log, mode, tasks);
             }
-            Some(ProjectCommands::ListQuotes { project_id, recipient_id }) => {
+            Some(ProjectCommands::ListMilestones { id }) => {
+                log.msg(format!("Listing milestones of project {}", id));
+                log.msg("--------------------".to_string());
+                let milestones = get_milestones(&db_pool, *id).await?;
+
+                log_list!(log, mode, milestones);
+            }
+            Some(ProjectCommands::ListQuotes { project_id, recipient_id, status }) => {
                 log.msg("Listing all quotes".to_string());
                 log.msg("------------------".to_string());
                 
                 let quotes = match (project_id, recipient_id) {
-                    (None, None) => sqlx::query_as!(Quote, "SELECT * FROM quotes")
+                    (None, None) => sqlx::query_as!(Quote, "SELECT * FROM quotes ORDER BY project_id, revision")
                         .fetch_all(&db_pool)
                         .await?,
                     _ => sqlx::query_as!(
@@ -436,6 +776,7 @@ async fn main() -> Result<()> {
                         SELECT * FROM quotes
                         WHERE ($1 IS NULL OR project_id = $1)
                         AND ($2 IS NULL OR recipient_id = $2)
+                        ORDER BY project_id, revision
                         "#,
                         project_id,
                         recipient_id
@@ -444,9 +785,68 @@ async fn main() -> Result<()> {
                         .await?
                 };
 
+                // Like invoices, expired depends on the current date
+                let quotes = quotes
+                    .into_iter()
+                    .filter(|quote| status.is_none_or(|status| quote.status() == status))
+                    .collect::<Vec<_>>();
+
                 log_list!(log, mode, quotes);
             }
-            Some(ProjectCommands::ListInvoices { contract_id, project_id, quote_id, recipient_id  }) => {
+            Some(ProjectCommands::MakeCreditNote { args }) => {
+                log.msg(format!("Making credit note for invoice {}", args.invoice_id));
+                let credit_note_url = make_credit_note(&db_pool, args).await?;
+                log.print("Credit note made, url:".to_string(), credit_note_url, true);
+            }
+            Some(ProjectCommands::RenderCreditNote { id }) => {
+                log.msg(format!("Rendering credit note {}", id));
+                let credit_note_url = render_credit_note(&db_pool, *id).await?;
+                if mode == PrintMode::Json {
+                    log.print("".to_string(), Jchar::from('['), false);
+                }
+                log.print(
+                    "Credit note rendered, url:".to_string(),
+                    credit_note_url,
+                    mode != PrintMode::Json,
+                );
+                if mode == PrintMode::Json {
+                    log.print("".to_string(), Jchar::from(']'), false);
+                }
+            }
+            Some(ProjectCommands::GetCreditNote { id }) => {
+                log.msg(format!("Getting credit note with id {}", id));
+                let credit_note = get_credit_note(&db_pool, *id).await?;
+                log.print(format!("Got credit note {id}"), credit_note, true);
+            }
+            Some(ProjectCommands::ListCreditNotes { invoice_id }) => {
+                log.msg("Listing credit notes".to_string());
+                log.msg("--------------------".to_string());
+
+                let credit_notes = get_credit_notes(&db_pool, *invoice_id).await?;
+                log_list!(log, mode, credit_notes);
+            }
+            Some(ProjectCommands::PayInvoice { id, args }) => {
+                log.msg(format!("Registering payment of {} for invoice {}", args.amount, id));
+                let payment = pay_invoice(&db_pool, *id, args).await?;
+                log.print(format!("Payment registered for invoice {id}"), payment, true);
+            }
+            Some(ProjectCommands::ListPayments { invoice_id }) => {
+                log.msg(format!("Listing payments of invoice {}", invoice_id));
+                log.msg("--------------------".to_string());
+
+                let payments = get_payments(&db_pool, *invoice_id).await?;
+                log_list!(log, mode, payments);
+            }
+            Some(ProjectCommands::WriteOffInvoice { id }) => {
+                log.msg(format!("Writing off invoice {}", id));
+                let updated = write_off_invoice(&db_pool, *id).await?;
+                if updated == 0 {
+                    log.print(format!("Invoice {} not found or already written off", id), -1, true);
+                } else {
+                    log.print(format!("Invoice {id} written off"), id, true);
+                }
+            }
+            Some(ProjectCommands::ListInvoices { contract_id, project_id, quote_id, recipient_id, status }) => {
                 log.msg("Listing all invoices".to_string());
                 log.msg("--------------------".to_string());
 
@@ -474,6 +874,12 @@ async fn main() -> Result<()> {
                     }
                 };
 
+                // The status depends on the current date, so it is filtered after fetching
+                let invoices = invoices
+                    .into_iter()
+                    .filter(|invoice| status.is_none_or(|status| invoice.status() == status))
+                    .collect::<Vec<_>>();
+
                 log_list!(log, mode, invoices);
             }
             None => {
@@ -530,9 +936,8 @@ async fn main() -> Result<()> {
             Some(FinanceCommands::Report { report }) => {
                 log.msg(format!("Creating report {:?}", report));
 
-                // let report = create_report(&db_pool, report).await?;
-                // log.print("Report created".to_string(), report, true);
-                log.print("Report created".to_string(), -1, true);
+                let reports = create_report(&db_pool, report).await?;
+                log.print("Report created".to_string(), reports, true);
             }
             Some(FinanceCommands::AddQuery { query }) => {
                 log.msg(format!("Adding query {:?}", query));
@@ -548,6 +953,11 @@ async fn main() -> Result<()> {
                     log.print(format!("Query {id} updated"), id, true);
                 }
             }
+            Some(FinanceCommands::RunQuery { id }) => {
+                log.msg(format!("Running query {}", id));
+                let reports = run_query(&db_pool, *id).await?;
+                log.print("Report created".to_string(), reports, true);
+            }
             Some(FinanceCommands::Remove { id }) => {
                 log.msg(format!("Removing query {}", id));
                 if sqlx::query!(r#"DELETE FROM finance_reports WHERE id = ?"#, id)
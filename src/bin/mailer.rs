@@ -9,9 +9,16 @@ extern crate tokio;
 
 use address::Envelope;
 use casual_cli_lib::clapargs::InvoiceMakeArgs;
-use casual_cli_lib::models::{Account, Contract, Schedule};
-use casual_cli_lib::queries::make_invoice;
-use chrono::{Datelike, NaiveDateTime, Timelike};
+use casual_cli_lib::interval::Interval;
+use casual_cli_lib::models::{
+    Account, Contract, ContractRate, FinanceReport, IndexationSource, Invoice, InvoiceStatus,
+    QuoteStatus, Schedule,
+};
+use casual_cli_lib::queries::{
+    get_account, get_company, get_contract, get_contract_rates, get_invoice, get_quote,
+    index_contract, make_invoice, renew_contract, run_query,
+};
+use chrono::NaiveDateTime;
 use lettre::*;
 use transport::smtp;
 use transport::smtp::response::Response;
@@ -35,45 +42,6 @@ struct RawMessage {
     path: Option<PathBuf>,
 }
 
-fn add_months(date: NaiveDateTime, months: u32) -> Result<NaiveDateTime> {
-    let year = date.year();
-    let month = date.month();
-    let day = date.day();
-    let hour = date.hour();
-    let minute = date.minute();
-    let second = date.second();
-    let subsec_nanos = date.nanosecond();
-    let mut new_month = month + months;
-    let mut new_year = year;
-    let mut new_day = day;
-    while new_month > 12 {
-        new_month -= 12;
-        new_year += 1;
-    }
-    while new_month < 1 {
-        new_month += 12;
-        new_year -= 1;
-    }
-    if day > 28 {
-        while new_day > 28 {
-            new_day -= 1;
-            if new_day == 0 {
-                new_day = 28;
-                break;
-            }
-        }
-    }
-
-    Ok(NaiveDateTime::new(
-        chrono::NaiveDate::from_ymd_opt(new_year, new_month, new_day).ok_or(anyhow!(
-            "Error: add_months couldn't make new date {new_year}-{new_month}-{new_day}"
-        ))?,
-        chrono::NaiveTime::from_hms_nano_opt(hour, minute, second, subsec_nanos).ok_or(anyhow!(
-            "Error: add_months couldn't make new date T {hour}:{minute}:{second}:{subsec_nanos}"
-        ))?,
-    ))
-}
-
 async fn auto_schedule_contracts(db_pool: &SqlitePool) -> Result<()> {
     let contracts = sqlx::query_as!(Contract, "SELECT * FROM contracts")
         .fetch_all(db_pool)
@@ -83,7 +51,10 @@ async fn auto_schedule_contracts(db_pool: &SqlitePool) -> Result<()> {
 
     for contract in contracts {
         println!("Auto-renew: {:?}", contract.auto_renew);
-        if contract.auto_renew.unwrap_or(false) {
+        if contract.auto_renew.unwrap_or(false)
+            && contract.cancel_date.is_none()
+            && !contract.is_paused()
+        {
             println!("Processing contract: {:?}", contract);
             let sender = sqlx::query_as!(
                 Account,
@@ -99,14 +70,16 @@ async fn auto_schedule_contracts(db_pool: &SqlitePool) -> Result<()> {
             )
             .fetch_one(db_pool)
             .await?;
-            let invoice_peroid_months = contract.invoice_period_months.unwrap_or(1);
+            let invoice_period = Interval::months(
+                contract.invoice_period_months.unwrap_or(1).try_into()?,
+            );
             let start_date = contract
                 .start_date
                 .unwrap_or(chrono::Local::now().naive_local());
-            let end_date = contract.end_date.unwrap_or(add_months(
-                start_date,
-                invoice_peroid_months.try_into().unwrap(),
-            )?);
+            let end_date = match contract.end_date {
+                Some(end_date) => end_date,
+                None => invoice_period.add_to(start_date)?,
+            };
             let sender_email = sender
                 .email
                 .unwrap_or("kenrick@casualdevelopment.nl".to_string());
@@ -122,11 +95,24 @@ async fn auto_schedule_contracts(db_pool: &SqlitePool) -> Result<()> {
                     contract_id: Some(contract.id),
                     quote_id: None,
                     project_id: None,
+                    sender_id: None,
                     remarks: None,
                     discount: None,
+                    vat_percentage: None,
+                    time_entries: false,
+                    milestone_id: None,
+                    dry_run: false,
                 };
 
-                let filename = make_invoice(&db_pool, &invoice_make_args).await?;
+                // A contract that can't be invoiced yet, ie. because its first period hasn't
+                // begun, doesn't stop the other contracts
+                let filename = match make_invoice(&db_pool, &invoice_make_args).await {
+                    std::result::Result::Ok(filename) => filename,
+                    Err(e) => {
+                        println!("Error invoicing contract {}: {:?}", contract.id, e);
+                        continue;
+                    }
+                };
                 let filebody = fs::read(&filename)?;
                 let content_type = message::header::ContentType::parse("application/pdf").unwrap();
                 let attachment = message::Attachment::new(filename).body(filebody, content_type);
@@ -152,41 +138,673 @@ async fn auto_schedule_contracts(db_pool: &SqlitePool) -> Result<()> {
                             Casual Development", recipient_name, sender_name, end_date_string)).into()
                         ).singlepart(attachment)
                     ).unwrap();
-                let path = format!(
-                    "./mails/schedule/{}",
-                    chrono::Local::now()
-                        .naive_local()
-                        .format("%Y-%m-%d")
-                        .to_string()
-                );
-                let path_dir = std::path::Path::new(path.as_str());
-                if !path_dir.exists() || !path_dir.is_dir() {
-                    fs::create_dir_all(path_dir)?;
-                }
-                let file_transport =
-                    lettre::transport::file::FileTransport::with_envelope(path_dir);
-                file_transport.send(&email).unwrap();
-
-                let new_end_date = add_months(end_date, invoice_peroid_months.try_into().unwrap())?;
-                sqlx::query!(
-                    "UPDATE contracts SET end_date = ? WHERE id = ?",
-                    new_end_date,
-                    contract.id
-                )
-                .execute(db_pool)
-                .await?;
+                queue_email(&email)?;
+
+                renew_contract(db_pool, contract.id, Some(invoice_period)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Raise the rate of every running contract a year after it started or was last indexed, by
+// CCLI_INDEXATION_PERCENTAGE. Contracts aren't indexed automatically when it isn't set
+async fn auto_index_contracts(db_pool: &SqlitePool) -> Result<()> {
+    let Some(percentage) = env::var("CCLI_INDEXATION_PERCENTAGE")
+        .ok()
+        .filter(|percentage| !percentage.is_empty())
+    else {
+        return Ok(());
+    };
+    // An invalid percentage skips the indexation, it doesn't stop the other jobs of the mailer
+    let percentage = match percentage.parse::<f64>() {
+        std::result::Result::Ok(percentage) => percentage,
+        Err(e) => {
+            println!(
+                "Error indexing contracts, invalid CCLI_INDEXATION_PERCENTAGE \"{}\": {:?}",
+                percentage, e
+            );
+            return Ok(());
+        }
+    };
+    let now = chrono::Local::now().naive_local();
+    let contracts = sqlx::query_as!(
+        Contract,
+        r#"
+SELECT * FROM contracts
+WHERE cancel_date IS NULL
+AND monthly_rate IS NOT NULL
+AND start_date IS NOT NULL
+"#
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    for contract in contracts {
+        let Some(start_date) = contract.start_date else {
+            continue;
+        };
+        if contract.is_paused() {
+            continue;
+        }
+        if contract.end_date.is_some_and(|end_date| end_date < now)
+            && !contract.auto_renew.unwrap_or(false)
+        {
+            continue;
+        }
+
+        let last_indexed = get_contract_rates(db_pool, contract.id)
+            .await?
+            .last()
+            .map_or(start_date, |rate| rate.effective_date);
+        if Interval::months(12).add_to(last_indexed)? > now {
+            continue;
+        }
+
+        let rate = index_contract(db_pool, contract.id, percentage, IndexationSource::Percentage)
+            .await?;
+        println!(
+            "Indexed contract {} by {}%, from {} to {}",
+            contract.id, percentage, rate.old_monthly_rate, rate.new_monthly_rate
+        );
+    }
+
+    Ok(())
+}
+
+async fn build_rate_change_email(
+    db_pool: &SqlitePool,
+    contract: &Contract,
+    rate: &ContractRate,
+) -> Result<Message> {
+    let parties = get_mail_parties(db_pool, contract.sender_id, contract.recipient_id).await?;
+    let old_rate = contract.money(rate.old_monthly_rate);
+    let new_rate = contract.money(rate.new_monthly_rate);
+
+    let body = render_mail_template(
+        "contract-renewal",
+        &[
+            ("recipient_name", parties.recipient_name.clone()),
+            ("sender_name", parties.sender_name.clone()),
+            ("contract_type", contract.contract_type.clone().unwrap_or("".to_string())),
+            ("effective_date", rate.effective_date.format("%d-%m-%Y").to_string()),
+            ("percentage", rate.percentage.to_string()),
+            ("currency_symbol", new_rate.symbol()),
+            ("old_rate", old_rate.format_amount()),
+            ("new_rate", new_rate.format_amount()),
+        ],
+    )?;
+
+    build_email(&parties, "Contract renewal: new monthly rate", message::SinglePart::html(body), None)
+}
+
+// Mail the client about every rate change they haven't been informed of yet
+async fn auto_inform_rate_changes(db_pool: &SqlitePool) -> Result<()> {
+    let now = chrono::Local::now().naive_local();
+    let rates = sqlx::query_as!(
+        ContractRate,
+        "SELECT * FROM contract_rates WHERE informed_date IS NULL"
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    for rate in rates {
+        let contract = get_contract(db_pool, rate.contract_id).await?;
+        let email = match build_rate_change_email(db_pool, &contract, &rate).await {
+            std::result::Result::Ok(email) => email,
+            Err(e) => {
+                println!("Error building rate change email for contract {}: {:?}", contract.id, e);
+                continue;
             }
+        };
+
+        // Claim the rate change before queueing, so a second daemon run can't mail it again
+        let result = sqlx::query!(
+            "UPDATE contract_rates SET informed_date = ? WHERE id = ? AND informed_date IS NULL",
+            now,
+            rate.id
+        )
+        .execute(db_pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            continue;
         }
+
+        println!("Queueing rate change of contract {}", contract.id);
+        queue_email(&email)?;
+    }
+
+    Ok(())
+}
+
+// Write the email to today's schedule directory so process_scheduled_emails picks it up
+fn queue_email(email: &Message) -> Result<()> {
+    let path = format!(
+        "./mails/schedule/{}",
+        chrono::Local::now().naive_local().format("%Y-%m-%d")
+    );
+    let path_dir = std::path::Path::new(path.as_str());
+    if !path_dir.exists() || !path_dir.is_dir() {
+        fs::create_dir_all(path_dir)?;
     }
+    let file_transport = lettre::transport::file::FileTransport::with_envelope(path_dir);
+    file_transport.send(email)?;
+
     Ok(())
 }
 
+struct MailParties {
+    from: String,
+    to: String,
+    sender_name: String,
+    recipient_name: String,
+}
+
+async fn get_mail_parties(
+    db_pool: &SqlitePool,
+    sender_id: i64,
+    recipient_id: i64,
+) -> Result<MailParties> {
+    let sender = get_account(db_pool, sender_id).await?;
+    let recipient = get_account(db_pool, recipient_id).await?;
+    let recipient_email = recipient
+        .email
+        .ok_or(anyhow!("Recipient {recipient_id} has no email address"))?;
+    let recipient_name = recipient.name.unwrap_or("Client".to_string());
+
+    Ok(MailParties {
+        from: sender
+            .email
+            .unwrap_or("kenrick@casualdevelopment.nl".to_string()),
+        to: format!("{} <{}>", recipient_name, recipient_email),
+        sender_name: sender.name.unwrap_or("Casual Development".to_string()),
+        recipient_name,
+    })
+}
+
+fn build_email(
+    parties: &MailParties,
+    subject: &str,
+    body: message::SinglePart,
+    attachment: Option<&str>,
+) -> Result<Message> {
+    let mut multipart = message::MultiPart::mixed().singlepart(body);
+    if let Some(path) = attachment {
+        let filebody = fs::read(path)?;
+        let filename = std::path::Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path)
+            .to_string();
+        let content_type = message::header::ContentType::parse("application/pdf")?;
+        multipart = multipart.singlepart(message::Attachment::new(filename).body(filebody, content_type));
+    }
+
+    Ok(Message::builder()
+        .from(parties.from.parse()?)
+        .date(chrono::Local::now().into())
+        .reply_to("CD Mailer <no-reply@casualdevelopment.nl>".parse()?)
+        .to(parties.to.parse()?)
+        .subject(subject)
+        .multipart(multipart)?)
+}
+
+// The totals of every currency of the report under each other
+fn format_report(reports: &[FinanceReport]) -> String {
+    let date = |date: Option<NaiveDateTime>| match date {
+        Some(d) => d.format("%d-%m-%Y").to_string(),
+        None => "the beginning".to_string(),
+    };
+    let Some(first) = reports.first() else {
+        return "".to_string();
+    };
+
+    reports.iter().fold(
+        format!(
+            "Finance report from {} to {}\r\n",
+            date(first.from_date),
+            date(first.to_date)
+        ),
+        |text, report| {
+            let money = |amount: i64| report.money(amount).to_string();
+            format!(
+                "{text}\r\n\
+                {}\r\n\
+                Invoices: {}\r\n\
+                Revenue (excl. VAT): {}\r\n\
+                Discounts: {}\r\n\
+                VAT collected: {}\r\n\
+                Revenue (incl. VAT): {}\r\n\
+                Paid: {}\r\n\
+                Outstanding: {}\r\n\
+                Quotes: {}\r\n\
+                Quoted (excl. VAT): {}\r\n\
+                Quoted (incl. VAT): {}\r\n",
+                report.currency,
+                report.invoice_count,
+                money(report.revenue_before_vat),
+                money(report.discount_total),
+                money(report.vat_collected),
+                money(report.revenue_after_vat),
+                money(report.paid_total),
+                money(report.outstanding_total),
+                report.quote_count,
+                money(report.quoted_before_vat),
+                money(report.quoted_after_vat)
+            )
+        },
+    )
+}
+
+// Move a due item on to its next date, only the run that moves it executes the item. Returns
+// false when another run already claimed this date
+async fn claim_schedule_item(
+    db_pool: &SqlitePool,
+    item: &Schedule,
+    date: NaiveDateTime,
+    next_date: Option<NaiveDateTime>,
+) -> Result<bool> {
+    let result = sqlx::query!(
+        "UPDATE schedule SET date = ? WHERE id = ? AND date = ?",
+        next_date,
+        item.id,
+        date
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// Do the work of a due schedule item, returns None when the item has nothing to execute or was
+// already claimed. Everything that can fail is checked before the item is claimed, and the item
+// is claimed before an invoice is made so a failure can't invoice the same period twice
+async fn execute_schedule_item(
+    db_pool: &SqlitePool,
+    item: &Schedule,
+    date: NaiveDateTime,
+    next_date: Option<NaiveDateTime>,
+) -> Result<Option<Message>> {
+    if let Some(contract_id) = item.contract_id {
+        let contract = sqlx::query_as!(
+            Contract,
+            "SELECT * FROM contracts WHERE id = ?",
+            contract_id
+        )
+        .fetch_one(db_pool)
+        .await?;
+        let parties = get_mail_parties(db_pool, contract.sender_id, contract.recipient_id).await?;
+        let invoice_make_args = InvoiceMakeArgs {
+            contract_id: Some(contract_id),
+            quote_id: None,
+            project_id: None,
+            sender_id: None,
+            remarks: None,
+            discount: None,
+            vat_percentage: None,
+            time_entries: false,
+            milestone_id: None,
+            dry_run: false,
+        };
+        if !claim_schedule_item(db_pool, item, date, next_date).await? {
+            return Ok(None);
+        }
+        let filename = make_invoice(db_pool, &invoice_make_args).await?;
+        let body = format!(
+            "Hello {},\r\n\
+            \r\n\
+            Attached you will find the invoice for your contract with {}.\r\n\
+            \r\n\
+            Best regards,\r\n\
+            {}",
+            parties.recipient_name, parties.sender_name, parties.sender_name
+        );
+
+        return Ok(Some(build_email(
+            &parties,
+            "Invoice",
+            message::SinglePart::plain(body),
+            Some(&filename),
+        )?));
+    }
+
+    if let Some(invoice_id) = item.invoice_id {
+        let invoice = get_invoice(db_pool, invoice_id).await?;
+
+        // A paid, credited or written off invoice needs no reminder, the occurrence is skipped
+        if matches!(invoice.status(), InvoiceStatus::Paid | InvoiceStatus::WrittenOff) {
+            claim_schedule_item(db_pool, item, date, next_date).await?;
+            return Ok(None);
+        }
+
+        let parties = get_mail_parties(db_pool, invoice.sender_id, invoice.recipient_id).await?;
+        let due_date = match invoice.payment_due_date {
+            Some(date) => format!(" before {}", date.format("%d-%m-%Y")),
+            None => "".to_string(),
+        };
+        let body = format!(
+            "Hello {},\r\n\
+            \r\n\
+            This is a friendly reminder of invoice {}, please make sure it is paid{}.\r\n\
+            \r\n\
+            Best regards,\r\n\
+            {}",
+            parties.recipient_name, invoice.invoice_number, due_date, parties.sender_name
+        );
+
+        let email = build_email(
+            &parties,
+            &format!("Reminder: invoice {}", invoice.invoice_number),
+            message::SinglePart::plain(body),
+            invoice.invoice_url.as_deref(),
+        )?;
+
+        return Ok(claim_schedule_item(db_pool, item, date, next_date)
+            .await?
+            .then_some(email));
+    }
+
+    if let Some(quote_id) = item.quote_id {
+        let quote = get_quote(db_pool, quote_id).await?;
+
+        // An answered or expired quote needs no reminder
+        if quote.status() != QuoteStatus::Sent {
+            return Ok(None);
+        }
+
+        let parties = get_mail_parties(db_pool, quote.sender_id, quote.recipient_id).await?;
+        let expire_date = match quote.expire_date {
+            Some(date) => format!(" It is valid until {}.", date.format("%d-%m-%Y")),
+            None => "".to_string(),
+        };
+        let body = format!(
+            "Hello {},\r\n\
+            \r\n\
+            This is a friendly reminder of the quote we sent you.{}\r\n\
+            \r\n\
+            Best regards,\r\n\
+            {}",
+            parties.recipient_name, expire_date, parties.sender_name
+        );
+
+        let email = build_email(
+            &parties,
+            "Reminder: quote",
+            message::SinglePart::plain(body),
+            quote.quote_url.as_deref(),
+        )?;
+
+        return Ok(claim_schedule_item(db_pool, item, date, next_date)
+            .await?
+            .then_some(email));
+    }
+
+    if let Some(query_id) = item.query_id {
+        let report_to = env::var("MAIL_REPORT_TO")
+            .map_err(|_| anyhow!("MAIL_REPORT_TO must be set to mail finance reports"))?;
+        let reports = run_query(db_pool, query_id).await?;
+        let parties = MailParties {
+            from: report_to.clone(),
+            to: report_to,
+            sender_name: "CD Mailer".to_string(),
+            recipient_name: "".to_string(),
+        };
+
+        let email = build_email(
+            &parties,
+            &format!("Finance report of query {query_id}"),
+            message::SinglePart::plain(format_report(&reports)),
+            None,
+        )?;
+
+        return Ok(claim_schedule_item(db_pool, item, date, next_date)
+            .await?
+            .then_some(email));
+    }
+
+    Ok(None)
+}
+
 async fn auto_schedule_schedule(db_pool: &SqlitePool) -> Result<()> {
-    let schedule_items = sqlx::query_as!(Schedule, "SELECT * FROM schedule")
+    let now = chrono::Local::now().naive_local();
+    let schedule_items = sqlx::query_as!(
+        Schedule,
+        "SELECT * FROM schedule WHERE date IS NOT NULL AND date <= ?",
+        now
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    println!("Due schedule items: {:?}", schedule_items);
+
+    for item in schedule_items {
+        let Some(date) = item.date else {
+            continue;
+        };
+
+        // Move forward one interval from the due date, not from now, so every period runs once.
+        // Items without an interval run once.
+        let next_date = match &item.interval {
+            Some(interval) => match interval
+                .parse::<Interval>()
+                .and_then(|interval| interval.add_to(date))
+            {
+                std::result::Result::Ok(next_date) => Some(next_date),
+                Err(e) => {
+                    println!("Invalid interval of schedule item {}: {:?}", item.id, e);
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        // A failing item shouldn't stop the other items or the daemon
+        let email = match execute_schedule_item(db_pool, &item, date, next_date).await {
+            std::result::Result::Ok(Some(email)) => email,
+            std::result::Result::Ok(None) => {
+                println!("Schedule item {} has nothing to execute or already ran", item.id);
+                continue;
+            }
+            Err(e) => {
+                println!("Error executing schedule item {}: {:?}", item.id, e);
+                continue;
+            }
+        };
+        if let Err(e) = queue_email(&email) {
+            println!("Error queueing the email of schedule item {}: {:?}", item.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ReminderStep {
+    FirstReminder,
+    SecondReminder,
+    FinalNotice,
+}
+
+impl ReminderStep {
+    const ALL: [ReminderStep; 3] = [
+        ReminderStep::FirstReminder,
+        ReminderStep::SecondReminder,
+        ReminderStep::FinalNotice,
+    ];
+
+    // Used for the invoice_reminders.step column and the template file name
+    fn name(&self) -> &'static str {
+        match self {
+            ReminderStep::FirstReminder => "first-reminder",
+            ReminderStep::SecondReminder => "second-reminder",
+            ReminderStep::FinalNotice => "final-notice",
+        }
+    }
+
+    fn subject(&self, invoice_number: &str) -> String {
+        match self {
+            ReminderStep::FirstReminder => format!("Reminder: invoice {invoice_number}"),
+            ReminderStep::SecondReminder => format!("Second reminder: invoice {invoice_number}"),
+            ReminderStep::FinalNotice => format!("Final notice: invoice {invoice_number}"),
+        }
+    }
+}
+
+// How long after the due date each reminder step is mailed, ie. MAIL_REMINDER_CADENCE="7d,14d,1m"
+fn get_reminder_cadence() -> Result<Vec<(ReminderStep, Interval)>> {
+    let cadence = env::var("MAIL_REMINDER_CADENCE").unwrap_or("7d,14d,1m".to_string());
+    let intervals = cadence
+        .split(',')
+        .map(|interval| interval.parse::<Interval>())
+        .collect::<Result<Vec<_>>>()?;
+
+    if intervals.len() != ReminderStep::ALL.len() {
+        return Err(anyhow!(
+            "MAIL_REMINDER_CADENCE \"{cadence}\" needs an interval for the first reminder, second reminder and final notice"
+        ));
+    }
+
+    Ok(ReminderStep::ALL.into_iter().zip(intervals).collect())
+}
+
+// Fill the %%name%% placeholders of a template under MAIL_TEMPLATE_DIR
+fn render_mail_template(name: &str, values: &[(&str, String)]) -> Result<String> {
+    let template_dir = env::var("MAIL_TEMPLATE_DIR").unwrap_or("./templates/mails".to_string());
+    let language = env::var("MAIL_LANGUAGE").unwrap_or("EN".to_string());
+    let path = format!("{template_dir}/{language}-{name}.html");
+    let template =
+        fs::read_to_string(&path).map_err(|e| anyhow!("Failed to read mail template {path}: {e}"))?;
+
+    Ok(values.iter().fold(template, |html, (key, value)| {
+        html.replace(&format!("%%{key}%%"), value)
+    }))
+}
+
+async fn build_reminder_email(
+    db_pool: &SqlitePool,
+    invoice: &Invoice,
+    step: ReminderStep,
+    days_overdue: i64,
+) -> Result<Message> {
+    let parties = get_mail_parties(db_pool, invoice.sender_id, invoice.recipient_id).await?;
+    let sender_iban = match get_account(db_pool, invoice.sender_id).await?.company_id {
+        Some(company_id) => get_company(db_pool, company_id).await?.iban,
+        None => None,
+    };
+    let outstanding = invoice.money(invoice.amount_due() - invoice.paid_total);
+
+    let body = render_mail_template(
+        &format!("invoice-{}", step.name()),
+        &[
+            ("recipient_name", parties.recipient_name.clone()),
+            ("sender_name", parties.sender_name.clone()),
+            ("sender_iban", sender_iban.unwrap_or("".to_string())),
+            ("invoice_number", invoice.invoice_number.clone()),
+            (
+                "due_date",
+                invoice
+                    .payment_due_date
+                    .map_or("".to_string(), |date| date.format("%d-%m-%Y").to_string()),
+            ),
+            ("days_overdue", days_overdue.to_string()),
+            ("currency_symbol", outstanding.symbol()),
+            ("outstanding", outstanding.format_amount()),
+        ],
+    )?;
+
+    build_email(
+        &parties,
+        &step.subject(&invoice.invoice_number),
+        message::SinglePart::html(body),
+        invoice.invoice_url.as_deref(),
+    )
+}
+
+async fn auto_schedule_reminders(db_pool: &SqlitePool) -> Result<()> {
+    let now = chrono::Local::now().naive_local();
+    // An invalid cadence skips the reminders, it doesn't stop the other jobs of the mailer
+    let cadence = match get_reminder_cadence() {
+        std::result::Result::Ok(cadence) => cadence,
+        Err(e) => {
+            println!("Error scheduling invoice reminders: {:?}", e);
+            return Ok(());
+        }
+    };
+    let invoices = sqlx::query_as!(
+        Invoice,
+        r#"
+SELECT * FROM invoices
+WHERE payment_date IS NULL
+AND written_off_date IS NULL
+AND payment_due_date IS NOT NULL
+AND payment_due_date < ?
+"#,
+        now
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    for invoice in invoices {
+        let Some(due_date) = invoice.payment_due_date else {
+            continue;
+        };
+        if invoice.paid_total >= invoice.amount_due() {
+            continue;
+        }
+
+        // Only the latest step that is due gets mailed, a daemon that was down doesn't send a
+        // first reminder for an invoice that is already up for its final notice
+        let mut due_step = None;
+        for (step, interval) in &cadence {
+            if interval.add_to(due_date)? <= now {
+                due_step = Some(*step);
+            }
+        }
+        let Some(step) = due_step else {
+            continue;
+        };
+
+        let sent_steps = sqlx::query_scalar!(
+            "SELECT step FROM invoice_reminders WHERE invoice_id = ?",
+            invoice.id
+        )
         .fetch_all(db_pool)
         .await?;
+        let already_sent = ReminderStep::ALL
+            .iter()
+            .skip_while(|s| **s != step)
+            .any(|s| sent_steps.iter().any(|sent| sent == s.name()));
+        if already_sent {
+            continue;
+        }
+
+        let days_overdue = now.signed_duration_since(due_date).num_days();
+        let email = match build_reminder_email(db_pool, &invoice, step, days_overdue).await {
+            std::result::Result::Ok(email) => email,
+            Err(e) => {
+                println!("Error building {} for invoice {}: {:?}", step.name(), invoice.id, e);
+                continue;
+            }
+        };
+
+        // Claim the step before queueing, so a second daemon run can't mail it again
+        let step_name = step.name();
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO invoice_reminders (invoice_id, step, sent_date) VALUES (?, ?, ?)",
+            invoice.id,
+            step_name,
+            now
+        )
+        .execute(db_pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            continue;
+        }
+
+        println!("Queueing {} for invoice {}", step_name, invoice.id);
+        queue_email(&email)?;
+    }
 
-    println!("Schedule items: {:?}", schedule_items);
     Ok(())
 }
 
@@ -297,7 +915,10 @@ async fn main() -> Result<()> {
         sqlx::migrate!().run(&db_pool).await?;
 
         auto_schedule_contracts(&db_pool).await?;
+        auto_index_contracts(&db_pool).await?;
+        auto_inform_rate_changes(&db_pool).await?;
         auto_schedule_schedule(&db_pool).await?;
+        auto_schedule_reminders(&db_pool).await?;
 
         let emails = process_scheduled_emails(&date_string)?;
 
@@ -1,6 +1,13 @@
+use std::str::FromStr;
+
+use anyhow::Result;
 use clap::Args as ClapArgs;
 use chrono::NaiveDateTime;
 
+use crate::interval::Interval;
+use crate::minutes::Minutes;
+use crate::models::TaskPricing;
+
 #[derive(ClapArgs, Debug)]
 pub struct AddressCreateArgs {
     #[arg(short, long)]
@@ -151,6 +158,13 @@ pub struct ProjectCreateArgs {
     pub description: Option<String>,
     #[arg(short, long)]
     pub client_id: i64,
+    #[arg(long)]
+    pub start_date: Option<NaiveDateTime>,
+    #[arg(long)]
+    pub end_date: Option<NaiveDateTime>,
+    /// The date the project has to be delivered by
+    #[arg(long)]
+    pub deadline: Option<NaiveDateTime>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -161,6 +175,13 @@ pub struct ProjectUpdateArgs {
     pub description: Option<String>,
     #[arg(short, long)]
     pub client_id: Option<i64>,
+    #[arg(long)]
+    pub start_date: Option<NaiveDateTime>,
+    #[arg(long)]
+    pub end_date: Option<NaiveDateTime>,
+    /// The date the project has to be delivered by
+    #[arg(long)]
+    pub deadline: Option<NaiveDateTime>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -179,8 +200,36 @@ pub struct ProjectTaskCreateArgs {
     pub minutes_remaining: Option<i64>,
     #[arg(long)]
     pub minutes_billed: Option<i64>,
+    /// Without it an hourly task is priced by the rate card of the sender of the quote or invoice
     #[arg(long)]
     pub minute_rate: Option<i64>,
+    /// Defaults to the VAT percentage of the quote or invoice
+    #[arg(long)]
+    pub vat_percentage: Option<i64>,
+    /// Make this a subtask of another task of the project
+    #[arg(long)]
+    pub parent_id: Option<i64>,
+    /// Only top level tasks have a milestone, subtasks belong to the one of their parent
+    #[arg(long, conflicts_with = "parent_id")]
+    pub milestone_id: Option<i64>,
+    /// Where the task goes among the tasks with the same parent starting at 1, defaults to the end
+    #[arg(long)]
+    pub position: Option<i64>,
+    /// Price the task by the hour with the minute rate, at a fixed price or per unit
+    #[arg(long)]
+    pub pricing: Option<TaskPricing>,
+    /// The fixed price or the price of one unit in cents
+    #[arg(long)]
+    pub price: Option<i64>,
+    /// The number of units of a task priced per unit
+    #[arg(long)]
+    pub quantity: Option<f64>,
+    /// What a unit is, ie. "page" or "licence"
+    #[arg(long)]
+    pub unit: Option<String>,
+    /// The role the work is done in, ie. "dev" or "design", to pick the rate card
+    #[arg(long)]
+    pub role: Option<String>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -199,8 +248,75 @@ pub struct ProjectTaskUpdateArgs {
     pub minutes_remaining: Option<i64>,
     #[arg(long)]
     pub minutes_billed: Option<i64>,
+    /// Without it an hourly task is priced by the rate card of the sender of the quote or invoice
     #[arg(long)]
     pub minute_rate: Option<i64>,
+    /// Defaults to the VAT percentage of the quote or invoice
+    #[arg(long)]
+    pub vat_percentage: Option<i64>,
+    /// Make this a subtask of another task of the project, 0 makes it a top level task
+    #[arg(long)]
+    pub parent_id: Option<i64>,
+    /// Only top level tasks have a milestone, 0 removes it from its milestone
+    #[arg(long)]
+    pub milestone_id: Option<i64>,
+    /// Move the task to this position among the tasks with the same parent, starting at 1
+    #[arg(long)]
+    pub position: Option<i64>,
+    /// Price the task by the hour with the minute rate, at a fixed price or per unit
+    #[arg(long)]
+    pub pricing: Option<TaskPricing>,
+    /// The fixed price or the price of one unit in cents
+    #[arg(long)]
+    pub price: Option<i64>,
+    /// The number of units of a task priced per unit
+    #[arg(long)]
+    pub quantity: Option<f64>,
+    /// What a unit is, ie. "page" or "licence"
+    #[arg(long)]
+    pub unit: Option<String>,
+    /// The role the work is done in, ie. "dev" or "design", to pick the rate card
+    #[arg(long)]
+    pub role: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct MilestoneCreateArgs {
+    #[arg(short, long)]
+    pub project_id: i64,
+    #[arg(short, long)]
+    pub title: String,
+    #[arg(short, long)]
+    pub description: Option<String>,
+    #[arg(long)]
+    pub due_date: Option<NaiveDateTime>,
+    /// Where the milestone goes in the project starting at 1, defaults to the end
+    #[arg(long)]
+    pub position: Option<i64>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct MilestoneUpdateArgs {
+    #[arg(short, long)]
+    pub title: Option<String>,
+    #[arg(short, long)]
+    pub description: Option<String>,
+    #[arg(long)]
+    pub due_date: Option<NaiveDateTime>,
+    /// Move the milestone to this position, starting at 1
+    #[arg(long)]
+    pub position: Option<i64>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct TimeEntryCreateArgs {
+    /// ie. "1h30m", "45m" or "2h"
+    pub duration: Minutes,
+    /// When the work started, defaults to now
+    #[arg(short, long)]
+    pub date: Option<NaiveDateTime>,
+    #[arg(short, long)]
+    pub note: Option<String>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -227,6 +343,12 @@ pub struct ContractCreateArgs {
     pub monthly_rate: Option<i64>,
     #[arg(long)]
     pub contract_url: Option<String>,
+    /// The notice a client has to give to cancel, ie. "1m" or "30d"
+    #[arg(long)]
+    pub notice_period: Option<Interval>,
+    /// Defaults to EUR
+    #[arg(long)]
+    pub currency: Option<String>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -253,6 +375,111 @@ pub struct ContractUpdateArgs {
     pub monthly_rate: Option<i64>,
     #[arg(long)]
     pub contract_url: Option<String>,
+    /// The notice a client has to give to cancel, ie. "1m" or "30d"
+    #[arg(long)]
+    pub notice_period: Option<Interval>,
+    #[arg(long)]
+    pub currency: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ClauseCreateArgs {
+    /// ie. "Payment", "Liability" or "Termination"
+    #[arg(short, long)]
+    pub category: String,
+    #[arg(short, long)]
+    pub title: String,
+    #[arg(short, long)]
+    pub description: String,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ClauseUpdateArgs {
+    #[arg(short, long)]
+    pub category: Option<String>,
+    #[arg(short, long)]
+    pub title: Option<String>,
+    #[arg(short, long)]
+    pub description: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct RateCardCreateArgs {
+    #[arg(short, long)]
+    pub name: String,
+    /// Only for this sender company
+    #[arg(long)]
+    pub company_id: Option<i64>,
+    /// Only for this client account
+    #[arg(long)]
+    pub client_id: Option<i64>,
+    /// Only for tasks in this role, ie. "dev", "design" or "pm"
+    #[arg(short, long)]
+    pub role: Option<String>,
+    #[arg(long)]
+    pub minute_rate: i64,
+    #[arg(long)]
+    pub valid_from: Option<NaiveDateTime>,
+    #[arg(long)]
+    pub valid_until: Option<NaiveDateTime>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct RateCardUpdateArgs {
+    #[arg(short, long)]
+    pub name: Option<String>,
+    #[arg(long)]
+    pub company_id: Option<i64>,
+    #[arg(long)]
+    pub client_id: Option<i64>,
+    #[arg(short, long)]
+    pub role: Option<String>,
+    #[arg(long)]
+    pub minute_rate: Option<i64>,
+    #[arg(long)]
+    pub valid_from: Option<NaiveDateTime>,
+    #[arg(long)]
+    pub valid_until: Option<NaiveDateTime>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ContractTermCreateArgs {
+    /// Copy the term from a clause of the library
+    #[arg(long, conflicts_with_all = ["category", "title", "description"])]
+    pub clause_id: Option<i64>,
+    #[arg(short, long, required_unless_present = "clause_id")]
+    pub category: Option<String>,
+    #[arg(short, long, required_unless_present = "clause_id")]
+    pub title: Option<String>,
+    #[arg(short, long, required_unless_present = "clause_id")]
+    pub description: Option<String>,
+    /// Where the term goes in the contract starting at 1, defaults to the end
+    #[arg(short, long)]
+    pub position: Option<i64>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ContractTermUpdateArgs {
+    #[arg(short, long)]
+    pub category: Option<String>,
+    #[arg(short, long)]
+    pub title: Option<String>,
+    #[arg(short, long)]
+    pub description: Option<String>,
+    /// Move the term to this position, starting at 1
+    #[arg(short, long)]
+    pub position: Option<i64>,
+}
+
+#[derive(ClapArgs, Debug)]
+#[group(required = true, multiple = false)]
+pub struct ContractIndexArgs {
+    /// Raise the monthly rate by a percentage, ie. 3.5
+    #[arg(short, long)]
+    pub percentage: Option<f64>,
+    /// Raise the monthly rate by the yearly change of the consumer price index, ie. 3.1
+    #[arg(long)]
+    pub cpi: Option<f64>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -279,6 +506,10 @@ pub struct QuoteCreateArgs {
     pub total_after_vat: Option<i64>,
     #[arg(short, long)]
     pub quote_url: Option<String>,
+    #[arg(long)]
+    pub expire_date: Option<NaiveDateTime>,
+    #[arg(long)]
+    pub reverse_charge: bool,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -305,20 +536,41 @@ pub struct QuoteUpdateArgs {
     pub total_after_vat: Option<i64>,
     #[arg(short, long)]
     pub quote_url: Option<String>,
+    #[arg(long)]
+    pub expire_date: Option<NaiveDateTime>,
+    #[arg(long)]
+    pub reverse_charge: Option<bool>,
 }
 
 #[derive(ClapArgs, Debug)]
 pub struct QuoteMakeArgs {
     #[arg(short, long)]
     pub project_id: i64,
+    /// Defaults to CCLI_DEFAULT_SENDER_ID
+    #[arg(short, long)]
+    pub sender_id: Option<i64>,
     #[arg(short, long)]
     pub remarks: Option<String>,
     #[arg(short, long)]
     pub discount: Option<i64>,
+    /// The rate of tasks without their own, defaults to CCLI_DEFAULT_VAT_PERCENTAGE
     #[arg(short, long)]
     pub vat_percentage: Option<i64>,
     #[arg(short, long)]
     pub currency: Option<String>,
+    /// The days the quote is valid, defaults to CCLI_QUOTE_VALID_DAYS or else 30
+    #[arg(long)]
+    pub valid_days: Option<i64>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct QuoteAcceptArgs {
+    /// Who accepted the quote, defaults to the name of the recipient
+    #[arg(short, long)]
+    pub accepted_by: Option<String>,
+    /// Make the invoice from the quote right away
+    #[arg(short, long)]
+    pub invoice: bool,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -357,6 +609,14 @@ pub struct InvoiceCreateArgs {
     pub invoice_url: Option<String>,
     #[arg(long)]
     pub payment_request_url: Option<String>,
+    #[arg(long)]
+    pub reverse_charge: bool,
+    /// The first day of the contract period the invoice covers
+    #[arg(long)]
+    pub period_start: Option<NaiveDateTime>,
+    /// The day after the contract period the invoice covers
+    #[arg(long)]
+    pub period_end: Option<NaiveDateTime>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -395,6 +655,8 @@ pub struct InvoiceUpdateArgs {
     pub invoice_url: Option<String>,
     #[arg(long)]
     pub payment_request_url: Option<String>,
+    #[arg(long)]
+    pub reverse_charge: Option<bool>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -405,10 +667,106 @@ pub struct InvoiceMakeArgs {
     pub project_id: Option<i64>,
     #[arg(short, long)]
     pub contract_id: Option<i64>,
+    /// Defaults to the sender of the quote or contract, or else CCLI_DEFAULT_SENDER_ID
+    #[arg(short, long)]
+    pub sender_id: Option<i64>,
     #[arg(short, long)]
     pub remarks: Option<String>,
     #[arg(short, long)]
     pub discount: Option<i64>,
+    /// The rate of tasks without their own, defaults to the rate of the quote or else
+    /// CCLI_DEFAULT_VAT_PERCENTAGE
+    #[arg(short, long)]
+    pub vat_percentage: Option<i64>,
+    /// Bill the time entries that aren't billed yet instead of the minutes spent
+    #[arg(short, long)]
+    pub time_entries: bool,
+    /// Only bill the tasks of this milestone of the project, to invoice it in instalments
+    #[arg(long)]
+    pub milestone_id: Option<i64>,
+    /// Print the lines that would be billed without making the invoice
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct CreditNoteMakeArgs {
+    #[arg(short, long)]
+    pub invoice_id: i64,
+    /// A credited line as "description=amount" in cents before VAT, or "description=amount@rate"
+    /// for another VAT rate than the invoice, repeat for more lines.
+    /// Without lines the whole invoice is credited
+    #[arg(short, long)]
+    pub line: Vec<CreditLine>,
+    #[arg(short, long)]
+    pub remarks: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CreditLine {
+    pub description: String,
+    pub amount: i64,
+    pub vat_percentage: Option<i64>,
+}
+
+impl FromStr for CreditLine {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (description, amount) = value
+            .rsplit_once('=')
+            .ok_or(anyhow::anyhow!("Invalid line \"{value}\", use \"description=amount\""))?;
+        let (amount, vat_percentage) = match amount.split_once('@') {
+            Some((amount, vat_percentage)) => (amount, Some(vat_percentage)),
+            None => (amount, None),
+        };
+        let amount = amount
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| anyhow::anyhow!("Invalid amount in line \"{value}\", use cents"))?;
+        let vat_percentage = vat_percentage
+            .map(|vat_percentage| {
+                vat_percentage.trim().parse::<i64>().map_err(|_| {
+                    anyhow::anyhow!("Invalid VAT rate in line \"{value}\", use a percentage")
+                })
+            })
+            .transpose()?;
+
+        if amount <= 0 {
+            return Err(anyhow::anyhow!("The amount of line \"{value}\" must be positive"));
+        }
+
+        Ok(CreditLine {
+            description: description.trim().to_string(),
+            amount,
+            vat_percentage,
+        })
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct PaymentCreateArgs {
+    /// The amount paid in cents
+    #[arg(short, long)]
+    pub amount: i64,
+    /// Defaults to now
+    #[arg(short, long)]
+    pub date: Option<NaiveDateTime>,
+    /// ie. "bank", "cash" or "ideal"
+    #[arg(long)]
+    pub method: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct InvoiceSequenceArgs {
+    /// ie. "{YYYY}{seq:05}", "CD-{YY}{MM}-{seq:04}" or "INV{seq}"
+    #[arg(short, long)]
+    pub format: Option<String>,
+    /// Start again at 1 every year
+    #[arg(short, long)]
+    pub yearly_reset: Option<bool>,
+    #[arg(short, long)]
+    pub next_number: Option<i64>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -425,9 +783,9 @@ pub struct ScheduleCreateArgs {
     pub query_id: Option<i64>,
     #[arg(short, long)]
     pub date: Option<NaiveDateTime>,
-    /// ie. "7d", "1m", "1y"
+    /// ie. "7d", "2w", "1m", "1y" or "1m15d"
     #[arg(long)]
-    pub interval: Option<String>,
+    pub interval: Option<Interval>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -444,9 +802,9 @@ pub struct ScheduleUpdateArgs {
     pub query_id: Option<i64>,
     #[arg(short, long)]
     pub date: Option<NaiveDateTime>,
-    /// ie. "7d", "1m", "1y"
+    /// ie. "7d", "2w", "1m", "1y" or "1m15d"
     #[arg(long)]
-    pub interval: Option<String>,
+    pub interval: Option<Interval>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -481,9 +839,9 @@ pub struct FinanceCreateQueryArgs {
     pub account_id: Option<i64>,
     #[arg(short, long)]
     pub company_id: Option<i64>,
-    /// ie. "7d", "1m", "1y" like ScheduleCreateArgs.interval
+    /// ie. "7d", "2w", "1m", "1y" or "1m15d" like ScheduleCreateArgs.interval
     #[arg(short, long)]
-    pub range: Option<String>,
+    pub range: Option<Interval>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -492,7 +850,40 @@ pub struct FinanceUpdateQueryArgs {
     pub account_id: Option<i64>,
     #[arg(short, long)]
     pub company_id: Option<i64>,
-    /// ie. "7d", "1m", "1y" like ScheduleCreateArgs.interval
+    /// ie. "7d", "2w", "1m", "1y" or "1m15d" like ScheduleCreateArgs.interval
     #[arg(short, long)]
-    pub range: Option<String>,
+    pub range: Option<Interval>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_credit_lines() {
+        let line = "Hosting=1500".parse::<CreditLine>().unwrap();
+        assert_eq!(line.description, "Hosting");
+        assert_eq!(line.amount, 1500);
+        assert_eq!(line.vat_percentage, None);
+
+        let line = " Hosting = 1500 @ 9 ".parse::<CreditLine>().unwrap();
+        assert_eq!(line.description, "Hosting");
+        assert_eq!(line.amount, 1500);
+        assert_eq!(line.vat_percentage, Some(9));
+    }
+
+    #[test]
+    fn splits_credit_lines_on_the_last_equals_sign() {
+        let line = "Discount a=b=250".parse::<CreditLine>().unwrap();
+        assert_eq!(line.description, "Discount a=b");
+        assert_eq!(line.amount, 250);
+    }
+
+    #[test]
+    fn rejects_invalid_credit_lines() {
+        let values = ["Hosting", "Hosting=", "Hosting=abc", "Hosting=0", "Hosting=-5", "Hosting=100@x"];
+        for value in values {
+            assert!(value.parse::<CreditLine>().is_err(), "{value} should be invalid");
+        }
+    }
 }
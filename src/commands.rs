@@ -2,10 +2,16 @@ use std::any::type_name_of_val;
 use std::fmt::Debug;
 use clap::ValueEnum;
 use clap::{Parser, Subcommand};
+use chrono::NaiveDateTime;
 use serde::Serialize;
 
 use crate::clapargs::*;
+use crate::interval::Interval;
 use crate::models::*;
+use crate::minutes::Minutes;
+use crate::money::Money;
+use crate::queries::DocumentLine;
+use crate::quote_diff::QuoteDiff;
 
 #[derive(ValueEnum, Clone, Debug, PartialEq)]
 pub enum PrintMode {
@@ -177,6 +183,8 @@ impl ToHtml for Contract {
         let sender_id = self.sender_id;
         let status: String = if self.cancel_date.is_some() {
             "Cancelled".to_string()
+        } else if self.is_paused() {
+            "Paused".to_string()
         } else if self.auto_renew.is_some_and(|x| x) {
             "Auto Renew".to_string()
         } else if self.end_date.is_some() && self.start_date.is_some() {
@@ -198,12 +206,81 @@ impl ToHtml for Contract {
     }
 }
 
+impl ToHtml for ContractRate {
+    fn to_html(&self) -> String {
+        let id = self.id;
+        let contract_id = self.contract_id;
+        let percentage = self.percentage;
+        let source = &self.source;
+        let old_rate = Money::new(self.old_monthly_rate, "EUR");
+        let new_rate = Money::new(self.new_monthly_rate, "EUR");
+        let effective_date = self.effective_date.format("%d-%m-%Y");
+
+        format!("<span data-id=\"{id}\" data-contract-id=\"{contract_id}\" data-source=\"{source}\">{effective_date}: {old_rate} to {new_rate} (+{percentage}%)</span>")
+    }
+}
+
+impl ToHtml for Clause {
+    fn to_html(&self) -> String {
+        let id = self.id;
+        let category = &self.category;
+        let title = &self.title;
+
+        format!("<span data-id=\"{id}\" data-category=\"{category}\">{title}</span>")
+    }
+}
+
+impl ToHtml for RateCard {
+    fn to_html(&self) -> String {
+        let id = self.id;
+        let name = &self.name;
+        let hourly_rate = self.minute_rate as f64 * 60.0 / 100.0;
+        let company_id = self
+            .company_id
+            .map_or("".to_string(), |company_id| format!(" data-company-id=\"{company_id}\""));
+        let client_id = self
+            .client_id
+            .map_or("".to_string(), |client_id| format!(" data-client-id=\"{client_id}\""));
+        let role = self
+            .role
+            .as_ref()
+            .map_or("".to_string(), |role| format!(" data-role=\"{role}\""));
+
+        format!("<span data-id=\"{id}\"{company_id}{client_id}{role}>{name}: {hourly_rate:.2} per hour</span>")
+    }
+}
+
+impl ToHtml for ContractTerm {
+    fn to_html(&self) -> String {
+        let id = self.id;
+        let contract_id = self.contract_id;
+        let position = self.position;
+        let category = &self.category;
+        let title = &self.title;
+
+        format!("<span data-id=\"{id}\" data-contract-id=\"{contract_id}\" data-category=\"{category}\">{position}. {title}</span>")
+    }
+}
+
 impl ToHtml for Project {
     fn to_html(&self) -> String {
         let id = self.id;
         let title = self.title.clone();
+        let status = self.status().as_str();
 
-        return format!("<span data-id=\"{id}\">{title}</span>");
+        return format!("<span data-id=\"{id}\" data-status=\"{status}\">{title}</span>");
+    }
+}
+
+impl ToHtml for ProjectStatusChange {
+    fn to_html(&self) -> String {
+        let id = self.id;
+        let project_id = self.project_id;
+        let old_status = &self.old_status;
+        let new_status = &self.new_status;
+        let created_at = self.created_at.format("%d-%m-%Y %H:%M");
+
+        format!("<span data-id=\"{id}\" data-project-id=\"{project_id}\" data-status=\"{new_status}\">{created_at}: {old_status} to {new_status}</span>")
     }
 }
 
@@ -211,8 +288,28 @@ impl ToHtml for ProjectTask {
     fn to_html(&self) -> String {
         let id = self.id;
         let title = self.title.clone();
+        let parent_id = self
+            .parent_id
+            .map_or("".to_string(), |parent_id| format!(" data-parent-id=\"{parent_id}\""));
+        let milestone_id = self.milestone_id.map_or("".to_string(), |milestone_id| {
+            format!(" data-milestone-id=\"{milestone_id}\"")
+        });
 
-        return format!("<span data-id=\"{id}\">{title}</span>");
+        return format!("<span data-id=\"{id}\"{parent_id}{milestone_id}>{title}</span>");
+    }
+}
+
+impl ToHtml for Milestone {
+    fn to_html(&self) -> String {
+        let id = self.id;
+        let project_id = self.project_id;
+        let position = self.position;
+        let title = &self.title;
+        let due_date = self
+            .due_date
+            .map_or("".to_string(), |due_date| format!(" ({})", due_date.format("%d-%m-%Y")));
+
+        format!("<span data-id=\"{id}\" data-project-id=\"{project_id}\">{position}. {title}{due_date}</span>")
     }
 }
 
@@ -226,7 +323,101 @@ impl ToHtml for Quote {
             .ok_or("#not-found")
             .expect("Unable to get quote_url");
 
-        return format!("<span data-id=\"{id}\" data-recipient-id=\"{recipient_id}\">Quote: <a href=\"{quote_url}\" target=\"_blank\">{quote_url}</a></span>");
+        let status = self
+            .status()
+            .to_possible_value()
+            .map_or("".to_string(), |value| value.get_name().to_string());
+
+        let revision = self.revision;
+
+        return format!("<span data-id=\"{id}\" data-recipient-id=\"{recipient_id}\" data-revision=\"{revision}\" data-status=\"{status}\">Quote v{revision}: <a href=\"{quote_url}\" target=\"_blank\">{quote_url}</a> {status}</span>");
+    }
+}
+
+impl ToHtml for QuoteDiff {
+    fn to_html(&self) -> String {
+        let amount = |amount: Option<i64>| {
+            amount.map_or("-".to_string(), |amount| Money::new(amount, &self.currency).to_string())
+        };
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| {
+                format!("<li data-change=\"{:?}\">{}: {} x {} -> {} x {} = {} -> {}</li>",
+                    line.change,
+                    line.title,
+                    line.old_quantity.map_or("-".to_string(), |x| x.to_string()),
+                    amount(line.old_unit_price),
+                    line.new_quantity.map_or("-".to_string(), |x| x.to_string()),
+                    amount(line.new_unit_price),
+                    amount(line.old_total),
+                    amount(line.new_total)
+                )
+            })
+            .collect::<String>();
+
+        format!("<div data-old-id=\"{}\" data-new-id=\"{}\">Quote v{} -> v{}<ul>{lines}</ul>Total before VAT: {} -> {}, discount: {} -> {}, total after VAT: {} -> {}</div>",
+            self.old_quote_id,
+            self.new_quote_id,
+            self.old_revision,
+            self.new_revision,
+            amount(Some(self.old_total_before_vat)),
+            amount(Some(self.new_total_before_vat)),
+            amount(Some(self.old_discount)),
+            amount(Some(self.new_discount)),
+            amount(Some(self.old_total_after_vat)),
+            amount(Some(self.new_total_after_vat))
+        )
+    }
+}
+
+impl ToHtml for QuoteLine {
+    fn to_html(&self) -> String {
+        format!("<span data-id=\"{}\" data-quote-id=\"{}\"{}>{}. {}: {} {} x {:.2} = {:.2} ({}% VAT)</span>",
+            self.id,
+            self.quote_id,
+            self.task_id.map_or("".to_string(), |x| format!(" data-task-id=\"{}\"", x)),
+            self.position,
+            self.title,
+            self.quantity,
+            self.unit,
+            self.unit_price as f64 / 100.0,
+            self.total as f64 / 100.0,
+            self.vat_percentage
+        )
+    }
+}
+
+impl ToHtml for InvoiceLine {
+    fn to_html(&self) -> String {
+        format!("<span data-id=\"{}\" data-invoice-id=\"{}\"{}{}>{}. {}: {} {} x {:.2} = {:.2} ({}% VAT)</span>",
+            self.id,
+            self.invoice_id,
+            self.task_id.map_or("".to_string(), |x| format!(" data-task-id=\"{}\"", x)),
+            self.contract_id.map_or("".to_string(), |x| format!(" data-contract-id=\"{}\"", x)),
+            self.position,
+            self.title,
+            self.quantity,
+            self.unit,
+            self.unit_price as f64 / 100.0,
+            self.total as f64 / 100.0,
+            self.vat_percentage
+        )
+    }
+}
+
+impl ToHtml for DocumentLine {
+    fn to_html(&self) -> String {
+        format!("<span{}{}>{}: {} {} x {:.2} = {:.2} ({}% VAT)</span>",
+            self.task_id.map_or("".to_string(), |x| format!(" data-task-id=\"{}\"", x)),
+            self.contract_id.map_or("".to_string(), |x| format!(" data-contract-id=\"{}\"", x)),
+            self.title,
+            self.quantity,
+            self.unit,
+            self.unit_price as f64 / 100.0,
+            self.total as f64 / 100.0,
+            self.vat_percentage
+        )
     }
 }
 
@@ -237,10 +428,57 @@ impl ToHtml for Invoice {
         let invoice_url = self
             .invoice_url
             .clone()
-            .ok_or("#not-found")
-            .expect("Unable to get quote_url");
+            .unwrap_or("#not-found".to_string());
+
+        let status = self
+            .status()
+            .to_possible_value()
+            .map_or("".to_string(), |value| value.get_name().to_string());
+
+        return format!("<span data-id=\"{id}\" data-recipient-id=\"{recipient_id}\" data-status=\"{status}\">Invoice: <a href=\"{invoice_url}\" target=\"_blank\">{invoice_url}</a> {status}</span>");
+    }
+}
 
-        return format!("<span data-id=\"{id}\" data-recipient-id=\"{recipient_id}\">Invoice: <a href=\"{invoice_url}\" target=\"_blank\">{invoice_url}</a></span>");
+impl ToHtml for CreditNote {
+    fn to_html(&self) -> String {
+        let credit_note_url = self
+            .credit_note_url
+            .clone()
+            .unwrap_or("#not-found".to_string());
+
+        format!("<span data-id=\"{}\" data-invoice-id=\"{}\" data-recipient-id=\"{}\">Credit note {}: <a href=\"{credit_note_url}\" target=\"_blank\">{credit_note_url}</a> {}</span>",
+            self.id,
+            self.invoice_id,
+            self.recipient_id,
+            self.credit_note_number,
+            self.money(self.total_after_vat)
+        )
+    }
+}
+
+impl ToHtml for Payment {
+    fn to_html(&self) -> String {
+        format!("<span data-id=\"{}\" data-invoice-id=\"{}\">Payment: {:.2} on {} {}</span>",
+            self.id,
+            self.invoice_id,
+            self.amount as f64 / 100.0,
+            self.date.format("%d-%m-%Y"),
+            self.method.clone().unwrap_or("".to_string())
+        )
+    }
+}
+
+impl ToHtml for TimeEntry {
+    fn to_html(&self) -> String {
+        format!("<span data-id=\"{}\" data-task-id=\"{}\"{}>Time: {} on {}{} {}</span>",
+            self.id,
+            self.task_id,
+            self.invoice_id.map_or("".to_string(), |x| format!(" data-invoice-id=\"{}\"", x)),
+            Minutes(self.minutes),
+            self.start_date.format("%d-%m-%Y %H:%M"),
+            if self.end_date.is_none() { " (running)" } else { "" },
+            self.note.clone().unwrap_or("".to_string())
+        )
     }
 }
 
@@ -274,10 +512,35 @@ impl ToHtml for FinanceReport {
             None => "never".to_string(),
         };
 
-        return format!("<span data-id=\"{id}\"{}{}{}>Report: {from_date} to {to_date}</span>",
+        let money = |amount: i64| self.money(amount).to_string();
+
+        return format!("<span data-id=\"{id}\"{}{}{} data-currency=\"{}\">Report: {from_date} to {to_date}</span>\
+            <dl>\
+            <dt>Invoices</dt><dd>{}</dd>\
+            <dt>Revenue (excl. VAT)</dt><dd>{}</dd>\
+            <dt>Discounts</dt><dd>{}</dd>\
+            <dt>VAT collected</dt><dd>{}</dd>\
+            <dt>Revenue (incl. VAT)</dt><dd>{}</dd>\
+            <dt>Paid</dt><dd>{}</dd>\
+            <dt>Outstanding</dt><dd>{}</dd>\
+            <dt>Quotes</dt><dd>{}</dd>\
+            <dt>Quoted (excl. VAT)</dt><dd>{}</dd>\
+            <dt>Quoted (incl. VAT)</dt><dd>{}</dd>\
+            </dl>",
             self.account_id.map_or("".to_string(), |x| format!(" data-account-id=\"{}\"", x)),
             self.company_id.map_or("".to_string(), |x| format!(" data-company-id=\"{}\"", x)),
-            self.query_id.map_or("".to_string(), |x| format!(" data-query-id=\"{}\"", x))
+            self.query_id.map_or("".to_string(), |x| format!(" data-query-id=\"{}\"", x)),
+            self.currency,
+            self.invoice_count,
+            money(self.revenue_before_vat),
+            money(self.discount_total),
+            money(self.vat_collected),
+            money(self.revenue_after_vat),
+            money(self.paid_total),
+            money(self.outstanding_total),
+            self.quote_count,
+            money(self.quoted_before_vat),
+            money(self.quoted_after_vat)
         );
     }
 }
@@ -294,6 +557,39 @@ impl ToHtml for FinanceQuery {
     }
 }
 
+impl ToHtml for InvoiceSequence {
+    fn to_html(&self) -> String {
+        format!("<span data-id=\"{}\" data-company-id=\"{}\" data-document=\"{}\" data-yearly-reset=\"{}\">Invoice sequence: {} next {}</span>",
+            self.id,
+            self.company_id,
+            self.document,
+            self.yearly_reset,
+            self.format,
+            self.next_number
+        )
+    }
+}
+
+impl ToHtml for Vec<CreditNote> {
+    fn to_html(&self) -> String {
+        let mut html = String::new();
+        for credit_note in self {
+            html.push_str(&credit_note.to_html());
+        }
+        html
+    }
+}
+
+impl ToHtml for Vec<Payment> {
+    fn to_html(&self) -> String {
+        let mut html = String::new();
+        for payment in self {
+            html.push_str(&payment.to_html());
+        }
+        html
+    }
+}
+
 impl ToHtml for Vec<String> {
     fn to_html(&self) -> String {
         let mut html = String::new();
@@ -553,6 +849,21 @@ pub enum AccountCommands {
         #[command(flatten)]
         contract: Box<ContractUpdateArgs>,
     },
+    /// Get the invoice or credit note numbering sequence of a company
+    GetInvoiceSequence {
+        company_id: i64,
+        #[arg(short, long, default_value = "invoice")]
+        document: SequenceDocument,
+    },
+    /// Set the invoice or credit note numbering format and next number of a company
+    SetInvoiceSequence {
+        company_id: i64,
+        #[arg(short, long, default_value = "invoice")]
+        document: SequenceDocument,
+        /// The sequence data
+        #[command(flatten)]
+        sequence: Box<InvoiceSequenceArgs>,
+    },
     /// Remove an account
     Remove {
         /// The account name
@@ -570,6 +881,104 @@ pub enum AccountCommands {
         /// The contract id
         id: i64,
     },
+    /// Extend the end date of a contract, by default by its invoice period
+    RenewContract {
+        id: i64,
+        /// ie. "1m" or "1y"
+        #[arg(short, long)]
+        period: Option<Interval>,
+    },
+    /// Cancel a contract, by default per the earliest date its notice period allows
+    CancelContract {
+        id: i64,
+        #[arg(short, long)]
+        effective_date: Option<NaiveDateTime>,
+    },
+    /// Stop invoicing a contract until it is resumed
+    PauseContract {
+        id: i64,
+    },
+    ResumeContract {
+        id: i64,
+    },
+    /// Raise the monthly rate of a contract by a percentage or CPI figure
+    IndexContract {
+        id: i64,
+        #[command(flatten)]
+        indexation: ContractIndexArgs,
+    },
+    /// List the rate changes of a contract
+    ListContractRates {
+        contract_id: i64,
+    },
+    GetClause {
+        id: i64,
+    },
+    /// Add a clause to the contract clause library
+    AddClause {
+        #[command(flatten)]
+        clause: Box<ClauseCreateArgs>,
+    },
+    UpdateClause {
+        id: i64,
+        #[command(flatten)]
+        clause: Box<ClauseUpdateArgs>,
+    },
+    /// Remove a clause from the library, contract terms copied from it are kept
+    RemoveClause {
+        id: i64,
+    },
+    ListClauses {
+        #[arg(short, long)]
+        category: Option<String>,
+    },
+    GetRateCard {
+        id: i64,
+    },
+    /// Add a rate card, tasks without a minute rate get theirs from the most specific card
+    AddRateCard {
+        #[command(flatten)]
+        rate_card: Box<RateCardCreateArgs>,
+    },
+    UpdateRateCard {
+        id: i64,
+        #[command(flatten)]
+        rate_card: Box<RateCardUpdateArgs>,
+    },
+    /// Remove a rate card, tasks keep the minute rate they got from it
+    RemoveRateCard {
+        id: i64,
+    },
+    ListRateCards {
+        #[arg(long)]
+        company_id: Option<i64>,
+        #[arg(long)]
+        client_id: Option<i64>,
+        #[arg(short, long)]
+        role: Option<String>,
+    },
+    /// Add a term to a contract, written out or copied from a clause of the library
+    AddContractTerm {
+        contract_id: i64,
+        #[command(flatten)]
+        term: Box<ContractTermCreateArgs>,
+    },
+    UpdateContractTerm {
+        id: i64,
+        #[command(flatten)]
+        term: Box<ContractTermUpdateArgs>,
+    },
+    RemoveContractTerm {
+        id: i64,
+    },
+    /// List the terms of a contract in order
+    ListContractTerms {
+        contract_id: i64,
+    },
+    /// Render the contract pdf from its terms and store it as the contract url
+    MakeContract {
+        id: i64,
+    },
     /// List all accounts (alias: `ls`)
     #[command(alias = "ls")]
     List {
@@ -621,6 +1030,25 @@ pub enum ProjectCommands {
         /// The project task id
         id: i64,
     },
+    /// Start a timer on a task, only one timer runs at a time
+    StartTimer {
+        task_id: i64,
+        #[arg(short, long)]
+        note: Option<String>,
+    },
+    /// Stop the running timer and add its minutes to the task
+    StopTimer,
+    /// Log time worked on a task, ie. "log-time 3 1h30m"
+    LogTime {
+        task_id: i64,
+        #[command(flatten)]
+        args: Box<TimeEntryCreateArgs>,
+    },
+    /// List the time entries, of one task or all
+    ListTimeEntries {
+        #[arg(short, long)]
+        task_id: Option<i64>,
+    },
     /// Remove a project
     Remove {
         /// The project id
@@ -630,6 +1058,23 @@ pub enum ProjectCommands {
         /// The project task id
         id: i64,
     },
+    GetMilestone {
+        id: i64,
+    },
+    /// Add a milestone to group the tasks of a project, it can be invoiced on its own
+    AddMilestone {
+        #[command(flatten)]
+        milestone: Box<MilestoneCreateArgs>,
+    },
+    UpdateMilestone {
+        id: i64,
+        #[command(flatten)]
+        milestone: Box<MilestoneUpdateArgs>,
+    },
+    /// Remove a milestone, its tasks are kept
+    RemoveMilestone {
+        id: i64,
+    },
     RemoveQuote {
         /// The quote id
         id: i64,
@@ -640,11 +1085,34 @@ pub enum ProjectCommands {
     },
     GetQuote {
         id: i64,
+        /// Print the lines the quote was made with instead of the quote
+        #[arg(short, long)]
+        lines: bool,
     },
     MakeQuote {
         #[command(flatten)]
         args: Box<QuoteMakeArgs>,
     },
+    /// Render the pdf of a quote again from its stored lines
+    RenderQuote {
+        id: i64,
+    },
+    /// Accept a quote, which opens its project
+    AcceptQuote {
+        id: i64,
+        #[command(flatten)]
+        args: Box<QuoteAcceptArgs>,
+    },
+    RejectQuote {
+        id: i64,
+    },
+    /// Show the changed lines and totals of a quote against the revision it superseded
+    DiffQuote {
+        id: i64,
+        /// Compare against this quote instead
+        #[arg(short, long)]
+        against: Option<i64>,
+    },
     UpdateQuote {
         id: i64,
         #[command(flatten)]
@@ -652,6 +1120,9 @@ pub enum ProjectCommands {
     },
     GetInvoice {
         id: i64,
+        /// Print the lines the invoice was made with instead of the invoice
+        #[arg(short, long)]
+        lines: bool,
     },
     UpdateInvoice {
         id: i64,
@@ -662,19 +1133,75 @@ pub enum ProjectCommands {
         #[command(flatten)]
         args: Box<InvoiceMakeArgs>,
     },
+    /// Render the pdf of an invoice again from its stored lines
+    RenderInvoice {
+        id: i64,
+    },
+    /// Credit (part of) an invoice with a credit note
+    MakeCreditNote {
+        #[command(flatten)]
+        args: Box<CreditNoteMakeArgs>,
+    },
+    /// Render the pdf of a credit note again from its stored lines
+    RenderCreditNote {
+        id: i64,
+    },
+    GetCreditNote {
+        id: i64,
+    },
+    /// List the credit notes, of one invoice or all
+    ListCreditNotes {
+        #[arg(short, long)]
+        invoice_id: Option<i64>,
+    },
+    /// Register a (partial) payment of an invoice
+    PayInvoice {
+        id: i64,
+        #[command(flatten)]
+        args: Box<PaymentCreateArgs>,
+    },
+    /// List the payments of an invoice
+    ListPayments {
+        invoice_id: i64,
+    },
+    /// Stop expecting payment of an invoice
+    WriteOffInvoice {
+        id: i64,
+    },
     /// List all projects (alias: `ls`)
     #[command(alias = "ls")]
-    List,
+    List {
+        #[arg(short, long)]
+        status: Option<ProjectStatus>,
+    },
+    /// Move a project to another status, ie. from in-progress to finished
+    SetStatus {
+        id: i64,
+        status: ProjectStatus,
+        #[arg(short, long)]
+        note: Option<String>,
+    },
+    /// List the status changes of a project
+    ListStatusChanges {
+        id: i64,
+    },
+    /// List the tasks of a project by milestone, subtasks follow their parent
     ListTasks {
         /// The project id
         id: i64,
     },
+    ListMilestones {
+        /// The project id
+        id: i64,
+    },
     ListQuotes {
         /// Project id
         #[arg(short, long)]
         project_id: Option<i64>,
         #[arg(short, long)]
-        recipient_id: Option<i64>
+        recipient_id: Option<i64>,
+        #[arg(short, long)]
+        status: Option<QuoteStatus>,
     },
     ListInvoices {
         /// Project id
@@ -686,6 +1213,8 @@ pub enum ProjectCommands {
         contract_id: Option<i64>,
         #[arg(short, long)]
         quote_id: Option<i64>,
+        #[arg(short, long)]
+        status: Option<InvoiceStatus>,
     },
 }
 
@@ -732,6 +1261,8 @@ pub enum FinanceCommands {
         #[command(flatten)]
         query: Box<FinanceUpdateQueryArgs>,
     },
+    /// Run a saved query, resolving its range against now
+    RunQuery { id: i64 },
     /// Remove an account
     Remove { id: i64 },
     RemoveQuery { id: i64 },
@@ -0,0 +1,193 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::{Days, Months, NaiveDateTime};
+use serde::Serialize;
+
+/// A calendar interval like "7d", "2w", "3m", "1y" or a compound "1m15d".
+/// Weeks are stored as days and years as months.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Interval {
+    pub months: u32,
+    pub days: u32,
+}
+
+impl Interval {
+    pub fn months(months: u32) -> Self {
+        Self { months, days: 0 }
+    }
+
+    pub fn days(days: u32) -> Self {
+        Self { months: 0, days }
+    }
+
+    /// Add the months first and then the days. A day that doesn't exist in the new month is
+    /// clamped to its last day (Jan 31 + 1m = Feb 28/29). A clamped date isn't a month end
+    /// afterwards, Feb 28 + 1m = Mar 28, so stepping a date on never drifts to the end of a month
+    pub fn add_to(&self, date: NaiveDateTime) -> Result<NaiveDateTime> {
+        date.checked_add_months(Months::new(self.months))
+            .and_then(|shifted| shifted.checked_add_days(Days::new(self.days.into())))
+            .ok_or(anyhow::anyhow!("Adding {self} to {date} is out of bounds"))
+    }
+
+    /// Subtract the months first and then the days, clamping like `add_to`
+    pub fn sub_from(&self, date: NaiveDateTime) -> Result<NaiveDateTime> {
+        date.checked_sub_months(Months::new(self.months))
+            .and_then(|shifted| shifted.checked_sub_days(Days::new(self.days.into())))
+            .ok_or(anyhow::anyhow!("Subtracting {self} from {date} is out of bounds"))
+    }
+}
+
+impl FromStr for Interval {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let mut interval = Interval::default();
+        let mut amount = String::new();
+
+        for c in value.chars() {
+            if c.is_ascii_digit() {
+                amount.push(c);
+                continue;
+            }
+
+            let n = amount.parse::<u32>().map_err(|_| {
+                anyhow::anyhow!("Invalid interval \"{value}\", expected a number before '{c}'")
+            })?;
+            amount.clear();
+
+            let overflow = || anyhow::anyhow!("Interval \"{value}\" is too large");
+            match c {
+                'd' => interval.days = interval.days.checked_add(n).ok_or_else(overflow)?,
+                'w' => {
+                    interval.days = n
+                        .checked_mul(7)
+                        .and_then(|days| interval.days.checked_add(days))
+                        .ok_or_else(overflow)?
+                }
+                'm' => interval.months = interval.months.checked_add(n).ok_or_else(overflow)?,
+                'y' => {
+                    interval.months = n
+                        .checked_mul(12)
+                        .and_then(|months| interval.months.checked_add(months))
+                        .ok_or_else(overflow)?
+                }
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Invalid interval unit '{c}' in \"{value}\", use d, w, m or y"
+                    ))
+                }
+            }
+        }
+
+        if !amount.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Invalid interval \"{value}\", {amount} is missing a unit (d, w, m or y)"
+            ));
+        }
+
+        if interval == Interval::default() {
+            return Err(anyhow::anyhow!(
+                "Invalid interval \"{value}\", use for example \"7d\", \"2w\", \"3m\", \"1y\" or \"1m15d\""
+            ));
+        }
+
+        Ok(interval)
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let years = self.months / 12;
+        let months = self.months % 12;
+
+        if years > 0 {
+            write!(f, "{years}y")?;
+        }
+        if months > 0 {
+            write!(f, "{months}m")?;
+        }
+        if self.days > 0 && self.days.is_multiple_of(7) {
+            write!(f, "{}w", self.days / 7)?;
+        } else if self.days > 0 {
+            write!(f, "{}d", self.days)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap()
+    }
+
+    fn add(interval: &str, to: NaiveDateTime) -> NaiveDateTime {
+        interval.parse::<Interval>().unwrap().add_to(to).unwrap()
+    }
+
+    #[test]
+    fn parses_units_and_compound_intervals() {
+        assert_eq!("7d".parse::<Interval>().unwrap(), Interval::days(7));
+        assert_eq!("2w".parse::<Interval>().unwrap(), Interval::days(14));
+        assert_eq!("3m".parse::<Interval>().unwrap(), Interval::months(3));
+        assert_eq!("1y".parse::<Interval>().unwrap(), Interval::months(12));
+        assert_eq!(
+            "1y2m1w3d".parse::<Interval>().unwrap(),
+            Interval { months: 14, days: 10 }
+        );
+        assert_eq!("1m15d".parse::<Interval>().unwrap().to_string(), "1m15d");
+    }
+
+    #[test]
+    fn rejects_invalid_intervals() {
+        for value in ["", "0d", "m", "7", "7x", "1m15"] {
+            assert!(value.parse::<Interval>().is_err(), "{value} should be invalid");
+        }
+    }
+
+    #[test]
+    fn clamps_jan_31_to_the_end_of_february() {
+        assert_eq!(add("1m", date(2025, 1, 31)), date(2025, 2, 28));
+        assert_eq!(add("1m", date(2024, 1, 31)), date(2024, 2, 29));
+        assert_eq!(add("2m", date(2025, 1, 31)), date(2025, 3, 31));
+    }
+
+    #[test]
+    fn steps_from_feb_29() {
+        assert_eq!(add("1m", date(2024, 2, 29)), date(2024, 3, 29));
+        assert_eq!(add("1y", date(2024, 2, 29)), date(2025, 2, 28));
+        assert_eq!(add("4y", date(2024, 2, 29)), date(2028, 2, 29));
+    }
+
+    #[test]
+    fn does_not_promote_a_clamped_date_to_month_end() {
+        let february = add("1m", date(2025, 1, 30));
+        assert_eq!(february, date(2025, 2, 28));
+        assert_eq!(add("1m", february), date(2025, 3, 28));
+    }
+
+    #[test]
+    fn adds_months_before_days() {
+        assert_eq!(add("1m15d", date(2025, 1, 31)), date(2025, 3, 15));
+        assert_eq!(add("1m1d", date(2024, 1, 31)), date(2024, 3, 1));
+        assert_eq!(add("1y2w", date(2024, 2, 29)), date(2025, 3, 14));
+    }
+
+    #[test]
+    fn subtracts_months_before_days() {
+        let interval = "1m15d".parse::<Interval>().unwrap();
+        assert_eq!(interval.sub_from(date(2025, 3, 31)).unwrap(), date(2025, 2, 13));
+        assert_eq!(Interval::months(1).sub_from(date(2024, 3, 31)).unwrap(), date(2024, 2, 29));
+    }
+}
@@ -1,4 +1,9 @@
 pub mod clapargs;
 pub mod commands;
+pub mod interval;
+pub mod minutes;
 pub mod models;
-pub mod queries;
\ No newline at end of file
+pub mod money;
+pub mod queries;
+pub mod quote_diff;
+pub mod vat;
\ No newline at end of file
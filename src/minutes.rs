@@ -0,0 +1,117 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// A duration of work in whole minutes like "1h30m", "45m" or "2h".
+/// A number without a unit is taken as minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Minutes(pub i64);
+
+impl FromStr for Minutes {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if let Ok(minutes) = value.parse::<i64>() {
+            return match minutes {
+                1.. => Ok(Minutes(minutes)),
+                _ => Err(anyhow::anyhow!("Invalid duration \"{value}\", it must be positive")),
+            };
+        }
+
+        let mut minutes: i64 = 0;
+        let mut amount = String::new();
+
+        for c in value.chars() {
+            if c.is_ascii_digit() {
+                amount.push(c);
+                continue;
+            }
+
+            let n = amount.parse::<i64>().map_err(|_| {
+                anyhow::anyhow!("Invalid duration \"{value}\", expected a number before '{c}'")
+            })?;
+            amount.clear();
+
+            let n = match c {
+                'h' => n.checked_mul(60),
+                'm' => Some(n),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Invalid duration unit '{c}' in \"{value}\", use h or m"
+                    ))
+                }
+            };
+            minutes = n
+                .and_then(|n| minutes.checked_add(n))
+                .ok_or(anyhow::anyhow!("Duration \"{value}\" is too large"))?;
+        }
+
+        if !amount.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Invalid duration \"{value}\", {amount} is missing a unit (h or m)"
+            ));
+        }
+
+        if minutes == 0 {
+            return Err(anyhow::anyhow!(
+                "Invalid duration \"{value}\", use for example \"1h30m\", \"45m\" or \"2h\""
+            ));
+        }
+
+        Ok(Minutes(minutes))
+    }
+}
+
+impl Display for Minutes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hours = self.0 / 60;
+        let minutes = self.0 % 60;
+
+        if hours > 0 {
+            write!(f, "{hours}h")?;
+        }
+        if minutes > 0 || hours == 0 {
+            write!(f, "{minutes}m")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hours_and_minutes() {
+        assert_eq!("1h30m".parse::<Minutes>().unwrap(), Minutes(90));
+        assert_eq!("45m".parse::<Minutes>().unwrap(), Minutes(45));
+        assert_eq!("2h".parse::<Minutes>().unwrap(), Minutes(120));
+        assert_eq!("1h90m".parse::<Minutes>().unwrap(), Minutes(150));
+        assert_eq!(" 1h ".parse::<Minutes>().unwrap(), Minutes(60));
+    }
+
+    #[test]
+    fn takes_a_number_without_unit_as_minutes() {
+        assert_eq!("90".parse::<Minutes>().unwrap(), Minutes(90));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        let values = ["", "0", "-5", "0m", "0h0m", "h", "1x", "1h30", "1.5h", "999999999999999999h"];
+        for value in values {
+            assert!(value.parse::<Minutes>().is_err(), "{value} should be invalid");
+        }
+    }
+
+    #[test]
+    fn displays_hours_and_minutes() {
+        assert_eq!(Minutes(90).to_string(), "1h30m");
+        assert_eq!(Minutes(45).to_string(), "45m");
+        assert_eq!(Minutes(120).to_string(), "2h");
+        assert_eq!(Minutes(0).to_string(), "0m");
+    }
+}
@@ -1,6 +1,9 @@
+use clap::ValueEnum;
 use serde::Serialize;
 use sqlx::types::chrono::NaiveDateTime;
 
+use crate::money::Money;
+
 #[derive(Debug, Serialize)]
 pub struct Address {
     pub id: i64,
@@ -53,6 +56,87 @@ pub struct Project {
     pub end_date: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub deadline: Option<NaiveDateTime>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectStatus {
+    PendingApproval,
+    Open,
+    InProgress,
+    OnHold,
+    Finished,
+    Billed,
+    Cancelled,
+}
+
+impl ProjectStatus {
+    const ALL: [ProjectStatus; 7] = [
+        ProjectStatus::PendingApproval,
+        ProjectStatus::Open,
+        ProjectStatus::InProgress,
+        ProjectStatus::OnHold,
+        ProjectStatus::Finished,
+        ProjectStatus::Billed,
+        ProjectStatus::Cancelled,
+    ];
+
+    /// The value of the projects.status column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectStatus::PendingApproval => "PENDING_APPROVAL",
+            ProjectStatus::Open => "OPEN",
+            ProjectStatus::InProgress => "IN_PROGRESS",
+            ProjectStatus::OnHold => "ON_HOLD",
+            ProjectStatus::Finished => "FINISHED",
+            ProjectStatus::Billed => "BILLED",
+            ProjectStatus::Cancelled => "CANCELLED",
+        }
+    }
+
+    pub fn from_column(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.as_str() == value)
+    }
+
+    /// The statuses a project can move to from this one, billed and cancelled projects are done
+    pub fn next(&self) -> &'static [ProjectStatus] {
+        match self {
+            ProjectStatus::PendingApproval => &[ProjectStatus::Open, ProjectStatus::Cancelled],
+            ProjectStatus::Open => &[
+                ProjectStatus::InProgress,
+                ProjectStatus::OnHold,
+                ProjectStatus::Cancelled,
+            ],
+            ProjectStatus::InProgress => &[
+                ProjectStatus::OnHold,
+                ProjectStatus::Finished,
+                ProjectStatus::Cancelled,
+            ],
+            ProjectStatus::OnHold => &[ProjectStatus::InProgress, ProjectStatus::Cancelled],
+            ProjectStatus::Finished => &[ProjectStatus::InProgress, ProjectStatus::Billed],
+            ProjectStatus::Billed | ProjectStatus::Cancelled => &[],
+        }
+    }
+}
+
+impl Project {
+    pub fn status(&self) -> ProjectStatus {
+        self.status
+            .as_deref()
+            .and_then(ProjectStatus::from_column)
+            .unwrap_or(ProjectStatus::PendingApproval)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectStatusChange {
+    pub id: i64,
+    pub project_id: i64,
+    pub old_status: String,
+    pub new_status: String,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
 }
 
 #[derive(Debug, Serialize)]
@@ -71,6 +155,91 @@ pub struct ProjectTask {
     pub is_completed: Option<bool>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub vat_percentage: Option<i64>,
+    pub parent_id: Option<i64>,
+    pub milestone_id: Option<i64>,
+    pub position: i64,
+    pub pricing: String,
+    pub price: Option<i64>,
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    pub quantity_billed: Option<f64>,
+    pub role: Option<String>,
+    pub rate_card_id: Option<i64>,
+}
+
+/// A minute rate of a sender company, for a client or for a role, valid between two dates
+#[derive(Debug, Serialize)]
+pub struct RateCard {
+    pub id: i64,
+    pub name: String,
+    pub company_id: Option<i64>,
+    pub client_id: Option<i64>,
+    pub role: Option<String>,
+    pub minute_rate: i64,
+    pub valid_from: Option<NaiveDateTime>,
+    pub valid_until: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// How a task is priced, by the minute_rate, at a fixed price or at a price per unit
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskPricing {
+    Hourly,
+    Fixed,
+    PerUnit,
+}
+
+impl TaskPricing {
+    const ALL: [TaskPricing; 3] = [TaskPricing::Hourly, TaskPricing::Fixed, TaskPricing::PerUnit];
+
+    /// The value of the tasks.pricing column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskPricing::Hourly => "HOURLY",
+            TaskPricing::Fixed => "FIXED",
+            TaskPricing::PerUnit => "PER_UNIT",
+        }
+    }
+
+    pub fn from_column(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pricing| pricing.as_str() == value)
+    }
+}
+
+impl ProjectTask {
+    pub fn pricing(&self) -> TaskPricing {
+        TaskPricing::from_column(&self.pricing).unwrap_or(TaskPricing::Hourly)
+    }
+}
+
+/// A group of tasks of a project that can be invoiced on its own
+#[derive(Debug, Serialize)]
+pub struct Milestone {
+    pub id: i64,
+    pub project_id: i64,
+    pub position: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub due_date: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Time worked on a task, a running timer has no end_date and counts 0 minutes until stopped
+#[derive(Debug, Serialize)]
+pub struct TimeEntry {
+    pub id: i64,
+    pub task_id: i64,
+    pub start_date: NaiveDateTime,
+    pub end_date: Option<NaiveDateTime>,
+    pub minutes: i64,
+    pub note: Option<String>,
+    pub invoice_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
 }
 
 #[derive(Debug, Serialize)]
@@ -89,6 +258,77 @@ pub struct Contract {
     pub contract_url: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub notice_period: Option<String>,
+    pub paused_date: Option<NaiveDateTime>,
+    pub resumed_date: Option<NaiveDateTime>,
+    pub currency: String,
+}
+
+impl Contract {
+    pub fn money(&self, amount: i64) -> Money {
+        Money::new(amount, &self.currency)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_date
+            .is_some_and(|paused| self.resumed_date.is_none_or(|resumed| resumed < paused))
+    }
+}
+
+/// A change of a contract's monthly rate by indexation, rates are in cents
+#[derive(Debug, Serialize)]
+pub struct ContractRate {
+    pub id: i64,
+    pub contract_id: i64,
+    pub old_monthly_rate: i64,
+    pub new_monthly_rate: i64,
+    pub percentage: f64,
+    pub source: String,
+    pub effective_date: NaiveDateTime,
+    pub informed_date: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Where the percentage of an indexation comes from, stored in contract_rates.source
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndexationSource {
+    Percentage,
+    Cpi,
+}
+
+impl IndexationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndexationSource::Percentage => "percentage",
+            IndexationSource::Cpi => "cpi",
+        }
+    }
+}
+
+/// A reusable clause of the contract clause library
+#[derive(Debug, Serialize)]
+pub struct Clause {
+    pub id: i64,
+    pub category: String,
+    pub title: String,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A term of a contract, copied from the clause it was added from if any
+#[derive(Debug, Serialize)]
+pub struct ContractTerm {
+    pub id: i64,
+    pub contract_id: i64,
+    pub clause_id: Option<i64>,
+    pub position: i64,
+    pub category: String,
+    pub title: String,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
 }
 
 #[derive(Debug, Serialize)]
@@ -109,6 +349,68 @@ pub struct Quote {
     pub quote_url: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub reverse_charge: bool,
+    pub accepted_date: Option<NaiveDateTime>,
+    pub accepted_by: Option<String>,
+    pub rejected_date: Option<NaiveDateTime>,
+    pub revision: i64,
+    pub superseded_by: Option<i64>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuoteStatus {
+    Draft,
+    Sent,
+    Accepted,
+    Rejected,
+    Superseded,
+    Expired,
+}
+
+impl Quote {
+    pub fn money(&self, amount: i64) -> Money {
+        Money::new(amount, &self.currency)
+    }
+
+    /// Derived like the invoice status, a quote that isn't answered before expire_date expires
+    pub fn status(&self) -> QuoteStatus {
+        let now = chrono::Local::now().naive_local();
+
+        if self.accepted_date.is_some() {
+            QuoteStatus::Accepted
+        } else if self.rejected_date.is_some() {
+            QuoteStatus::Rejected
+        } else if self.superseded_by.is_some() {
+            QuoteStatus::Superseded
+        } else if self.expire_date.is_some_and(|date| date < now) {
+            QuoteStatus::Expired
+        } else if self.send_date.is_none_or(|date| date > now) {
+            QuoteStatus::Draft
+        } else {
+            QuoteStatus::Sent
+        }
+    }
+}
+
+/// A line of a quote as it was made, `quantity` is in `unit`s and `unit_price` and `total` in cents
+#[derive(Debug, Serialize)]
+pub struct QuoteLine {
+    pub id: i64,
+    pub quote_id: i64,
+    pub position: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub quantity: f64,
+    pub unit: String,
+    pub unit_price: i64,
+    pub total: i64,
+    pub vat_percentage: i64,
+    pub task_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub milestone: Option<String>,
+    pub rate_card: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -133,6 +435,86 @@ pub struct Invoice {
     pub payment_request_url: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub paid_total: i64,
+    pub written_off_date: Option<NaiveDateTime>,
+    pub credited_total: i64,
+    pub reverse_charge: bool,
+    pub period_start: Option<NaiveDateTime>,
+    pub period_end: Option<NaiveDateTime>,
+    pub quote_discount: i64,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InvoiceStatus {
+    Draft,
+    Sent,
+    PartiallyPaid,
+    Paid,
+    Overdue,
+    WrittenOff,
+}
+
+impl Invoice {
+    pub fn money(&self, amount: i64) -> Money {
+        Money::new(amount, &self.currency)
+    }
+
+    /// The amount to pay in cents after credit notes
+    pub fn amount_due(&self) -> i64 {
+        self.total_after_vat - self.credited_total
+    }
+
+    /// Derived from the payments, due date and write off, so overdue follows the clock
+    pub fn status(&self) -> InvoiceStatus {
+        let now = chrono::Local::now().naive_local();
+
+        if self.written_off_date.is_some() {
+            InvoiceStatus::WrittenOff
+        } else if self.payment_date.is_some() || self.paid_total >= self.amount_due() {
+            InvoiceStatus::Paid
+        } else if self.paid_total > 0 {
+            InvoiceStatus::PartiallyPaid
+        } else if self.send_date.is_none_or(|date| date > now) {
+            InvoiceStatus::Draft
+        } else if self.payment_due_date.is_some_and(|date| date < now) {
+            InvoiceStatus::Overdue
+        } else {
+            InvoiceStatus::Sent
+        }
+    }
+}
+
+/// A line of an invoice as it was made, from a task or a contract period
+#[derive(Debug, Serialize)]
+pub struct InvoiceLine {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub position: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub quantity: f64,
+    pub unit: String,
+    pub unit_price: i64,
+    pub total: i64,
+    pub vat_percentage: i64,
+    pub task_id: Option<i64>,
+    pub contract_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub milestone: Option<String>,
+    pub rate_card: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Payment {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub amount: i64,
+    pub date: NaiveDateTime,
+    pub method: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
 }
 
 #[derive(Debug, Serialize)]
@@ -157,6 +539,25 @@ pub struct FinanceReport {
     pub query_id: Option<i64>,
     pub from_date: Option<NaiveDateTime>,
     pub to_date: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub invoice_count: i64,
+    pub quote_count: i64,
+    pub revenue_before_vat: i64,
+    pub discount_total: i64,
+    pub vat_collected: i64,
+    pub revenue_after_vat: i64,
+    pub paid_total: i64,
+    pub outstanding_total: i64,
+    pub quoted_before_vat: i64,
+    pub quoted_after_vat: i64,
+    pub currency: String,
+}
+
+impl FinanceReport {
+    pub fn money(&self, amount: i64) -> Money {
+        Money::new(amount, &self.currency)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -165,4 +566,80 @@ pub struct FinanceQuery {
     pub account_id: Option<i64>,
     pub company_id: Option<i64>,
     pub range: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreditNote {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub sender_id: i64,
+    pub recipient_id: i64,
+    pub credit_note_number: String,
+    pub send_date: Option<NaiveDateTime>,
+    pub remarks: Option<String>,
+    pub total_before_vat: i64,
+    pub vat_percentage: Option<i64>,
+    pub currency: String,
+    pub total_after_vat: i64,
+    pub credit_note_url: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub reverse_charge: bool,
+    pub discount: i64,
+}
+
+impl CreditNote {
+    pub fn money(&self, amount: i64) -> Money {
+        Money::new(amount, &self.currency)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreditNoteLine {
+    pub id: i64,
+    pub credit_note_id: i64,
+    pub description: String,
+    pub amount: i64,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub vat_percentage: i64,
+}
+
+/// The documents that are numbered by an invoice sequence
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SequenceDocument {
+    Invoice,
+    CreditNote,
+}
+
+impl SequenceDocument {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SequenceDocument::Invoice => "invoice",
+            SequenceDocument::CreditNote => "credit-note",
+        }
+    }
+
+    pub fn default_format(&self) -> &'static str {
+        match self {
+            SequenceDocument::Invoice => "{YYYY}{seq:05}",
+            SequenceDocument::CreditNote => "C{YYYY}{seq:05}",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceSequence {
+    pub id: i64,
+    pub company_id: i64,
+    pub document: String,
+    pub format: String,
+    pub yearly_reset: bool,
+    pub next_number: i64,
+    pub year: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
 }
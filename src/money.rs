@@ -0,0 +1,127 @@
+use std::fmt::Display;
+use std::ops::{Add, Neg, Sub};
+
+use serde::Serialize;
+
+/// An amount in minor units (cents) of a currency with two decimals.
+/// Percentages round half away from zero to a whole cent, so VAT is computed once per amount and
+/// every stored amount has the same scale.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Money {
+    pub amount: i64,
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(amount: i64, currency: &str) -> Self {
+        Self {
+            amount,
+            currency: currency.to_string(),
+        }
+    }
+
+    pub fn zero(currency: &str) -> Self {
+        Self::new(0, currency)
+    }
+
+    /// The given percentage of the amount, rounded half away from zero
+    pub fn percentage(&self, percentage: i64) -> Money {
+        let scaled = self.amount * percentage;
+        let rounded = if scaled >= 0 {
+            (scaled + 50) / 100
+        } else {
+            (scaled - 50) / 100
+        };
+
+        Money::new(rounded, &self.currency)
+    }
+
+    pub fn vat(&self, vat_percentage: i64) -> Money {
+        self.percentage(vat_percentage)
+    }
+
+    pub fn with_vat(&self, vat_percentage: i64) -> Money {
+        self.clone() + self.vat(vat_percentage)
+    }
+
+    pub fn symbol(&self) -> String {
+        match self.currency.as_str() {
+            "EUR" => "€".to_string(),
+            "USD" => "$".to_string(),
+            currency => currency.to_string(),
+        }
+    }
+
+    /// The amount in major units without a symbol, ie. "1234.50" or "-0.05"
+    pub fn format_amount(&self) -> String {
+        let sign = if self.amount < 0 { "-" } else { "" };
+        let amount = self.amount.unsigned_abs();
+
+        format!("{sign}{}.{:02}", amount / 100, amount % 100)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.symbol(), self.format_amount())
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        debug_assert_eq!(self.currency, other.currency, "Adding money in different currencies");
+        Money::new(self.amount + other.amount, &self.currency)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        debug_assert_eq!(self.currency, other.currency, "Subtracting money in different currencies");
+        Money::new(self.amount - other.amount, &self.currency)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money::new(-self.amount, &self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eur(amount: i64) -> Money {
+        Money::new(amount, "EUR")
+    }
+
+    #[test]
+    fn rounds_percentages_half_away_from_zero() {
+        assert_eq!(eur(250).percentage(21), eur(53));
+        assert_eq!(eur(-250).percentage(21), eur(-53));
+        assert_eq!(eur(238).percentage(21), eur(50));
+        assert_eq!(eur(-238).percentage(21), eur(-50));
+        assert_eq!(eur(2).percentage(21), eur(0));
+    }
+
+    #[test]
+    fn adds_vat_to_the_amount() {
+        assert_eq!(eur(10000).with_vat(21), eur(12100));
+        assert_eq!(eur(-10000).with_vat(9), eur(-10900));
+        assert_eq!(eur(10000).with_vat(0), eur(10000));
+    }
+
+    #[test]
+    fn formats_amounts_with_two_decimals() {
+        assert_eq!(eur(123450).format_amount(), "1234.50");
+        assert_eq!(eur(-5).format_amount(), "-0.05");
+        assert_eq!(eur(6050).to_string(), "€ 60.50");
+        assert_eq!(Money::new(100, "GBP").to_string(), "GBP 1.00");
+    }
+}
@@ -3,12 +3,18 @@ use std::str::FromStr;
 
 use anyhow::Result;
 use chrono::{Datelike, Months};
+use clap::ValueEnum;
+use serde::Serialize;
 use simple_pdf_generator::{Asset, AssetType, PrintOptions};
 use sqlx::SqlitePool;
 use struct_field_names_as_array::FieldNamesAsArray;
 
 use crate::clapargs::*;
+use crate::interval::Interval;
+use crate::minutes::Minutes;
 use crate::models::*;
+use crate::quote_diff::QuoteDiff;
+use crate::vat::{is_reverse_charge, VatBreakdown, REVERSE_CHARGE_NOTE};
 
 pub async fn add_address(db: &SqlitePool, address: &AddressCreateArgs) -> Result<i64> {
     let address_id = sqlx::query!(
@@ -81,6 +87,13 @@ INSERT INTO address (
     Ok(address_id)
 }
 
+pub async fn get_address(db: &SqlitePool, id: i64) -> Result<Address> {
+    sqlx::query_as!(Address, r#"SELECT * FROM address WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
 pub async fn get_account(db: &SqlitePool, id: i64) -> Result<Account> {
     sqlx::query_as!(Account, r#"SELECT * FROM accounts WHERE id = ?"#, id)
         .fetch_one(db)
@@ -274,6 +287,8 @@ Contract model:
 */
 
 pub async fn add_contract(db: &SqlitePool, contract: &ContractCreateArgs) -> Result<i64> {
+    let notice_period = contract.notice_period.map(|interval| interval.to_string());
+    let currency = contract.currency.clone().unwrap_or("EUR".to_string());
     let contract_id = sqlx::query!(r#"
 INSERT INTO contracts (
     sender_id,
@@ -281,15 +296,19 @@ INSERT INTO contracts (
     contract_type,
     invoice_period_months,
     monthly_rate,
-    contract_url
-) VALUES (?, ?, ?, ?, ?, ?)
+    contract_url,
+    notice_period,
+    currency
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
 "#,
         contract.sender_id,
         contract.recipient_id,
         contract.contract_type,
         contract.invoice_period_months,
         contract.monthly_rate,
-        contract.contract_url
+        contract.contract_url,
+        notice_period,
+        currency
     )
     .execute(db)
     .await?
@@ -379,6 +398,7 @@ pub async fn update_contract(
     id: i64,
     contract: &ContractUpdateArgs,
 ) -> Result<u64> {
+    let notice_period = contract.notice_period.map(|interval| interval.to_string());
     let result = sqlx::query!(
         r#"UPDATE contracts SET
         sender_id = COALESCE(?, sender_id),
@@ -391,7 +411,9 @@ pub async fn update_contract(
         cancel_date = COALESCE(?, cancel_date),
         invoice_period_months = COALESCE(?, invoice_period_months),
         monthly_rate = COALESCE(?, monthly_rate),
-        contract_url = COALESCE(?, contract_url)
+        contract_url = COALESCE(?, contract_url),
+        notice_period = COALESCE(?, notice_period),
+        currency = COALESCE(?, currency)
         WHERE id = ?"#,
         contract.sender_id,
         contract.recipient_id,
@@ -404,6 +426,8 @@ pub async fn update_contract(
         contract.invoice_period_months,
         contract.monthly_rate,
         contract.contract_url,
+        notice_period,
+        contract.currency,
         id
     )
     .execute(db)
@@ -412,43 +436,255 @@ pub async fn update_contract(
     Ok(result.rows_affected())
 }
 
-pub async fn get_project(db: &SqlitePool, id: i64) -> Result<Project> {
-    sqlx::query_as!(Project, r#"SELECT * FROM projects WHERE id = ?"#, id)
+/// Extend the end date of a contract by `period`, by default its invoice period. A contract
+/// without an end date first runs one period from its start
+pub async fn renew_contract(
+    db: &SqlitePool,
+    id: i64,
+    period: Option<Interval>,
+) -> Result<chrono::NaiveDateTime> {
+    let contract = get_contract(db, id).await?;
+    if let Some(cancel_date) = contract.cancel_date {
+        return Err(anyhow::anyhow!(
+            "Contract {id} is cancelled per {}, it can't be renewed",
+            cancel_date.format("%d-%m-%Y")
+        ));
+    }
+
+    let period = match period {
+        Some(period) => period,
+        None => Interval::months(contract.invoice_period_months.unwrap_or(1).max(1).try_into()?),
+    };
+    let end_date = match contract.end_date {
+        Some(end_date) => end_date,
+        None => period.add_to(
+            contract
+                .start_date
+                .unwrap_or(chrono::Local::now().naive_local()),
+        )?,
+    };
+    let new_end_date = period.add_to(end_date)?;
+
+    sqlx::query!(
+        "UPDATE contracts SET end_date = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        new_end_date,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(new_end_date)
+}
+
+/// Cancel a contract per `effective_date`, which can't be sooner than the notice period allows.
+/// Without a date the contract ends as soon as the notice period allows
+pub async fn cancel_contract(
+    db: &SqlitePool,
+    id: i64,
+    effective_date: Option<chrono::NaiveDateTime>,
+) -> Result<chrono::NaiveDateTime> {
+    let contract = get_contract(db, id).await?;
+    if let Some(cancel_date) = contract.cancel_date {
+        return Err(anyhow::anyhow!(
+            "Contract {id} is already cancelled per {}",
+            cancel_date.format("%d-%m-%Y")
+        ));
+    }
+
+    let notice_period = match &contract.notice_period {
+        Some(notice_period) => notice_period.parse::<Interval>()?,
+        None => Interval::default(),
+    };
+    let earliest_date = notice_period.add_to(chrono::Local::now().naive_local())?;
+    let cancel_date = effective_date.unwrap_or(earliest_date);
+
+    if cancel_date < earliest_date {
+        return Err(anyhow::anyhow!(
+            "Contract {id} has a notice period of {notice_period}, it can be cancelled per {} at the earliest",
+            earliest_date.format("%d-%m-%Y")
+        ));
+    }
+
+    sqlx::query!(
+        r#"UPDATE contracts SET
+        cancel_date = ?,
+        auto_renew = FALSE,
+        updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?"#,
+        cancel_date,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(cancel_date)
+}
+
+/// A paused contract isn't invoiced or renewed until it is resumed
+pub async fn pause_contract(db: &SqlitePool, id: i64) -> Result<chrono::NaiveDateTime> {
+    let contract = get_contract(db, id).await?;
+    if let Some(paused_date) = contract.paused_date.filter(|_| contract.is_paused()) {
+        return Err(anyhow::anyhow!(
+            "Contract {id} is already paused since {}",
+            paused_date.format("%d-%m-%Y")
+        ));
+    }
+
+    let paused_date = chrono::Local::now().naive_local();
+    sqlx::query!(
+        "UPDATE contracts SET paused_date = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        paused_date,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(paused_date)
+}
+
+/// Invoicing continues from the resume date, the paused time isn't billed
+pub async fn resume_contract(db: &SqlitePool, id: i64) -> Result<chrono::NaiveDateTime> {
+    let contract = get_contract(db, id).await?;
+    if !contract.is_paused() {
+        return Err(anyhow::anyhow!("Contract {id} is not paused"));
+    }
+
+    let resumed_date = chrono::Local::now().naive_local();
+    sqlx::query!(
+        "UPDATE contracts SET resumed_date = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        resumed_date,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(resumed_date)
+}
+
+pub async fn get_contract_rates(db: &SqlitePool, contract_id: i64) -> Result<Vec<ContractRate>> {
+    sqlx::query_as!(
+        ContractRate,
+        r#"SELECT * FROM contract_rates WHERE contract_id = ? ORDER BY effective_date, id"#,
+        contract_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+/// Raise the monthly rate of a contract by `percentage` as of now and keep the old rate in the
+/// history. The mailer informs the client of the new rate
+pub async fn index_contract(
+    db: &SqlitePool,
+    id: i64,
+    percentage: f64,
+    source: IndexationSource,
+) -> Result<ContractRate> {
+    if !percentage.is_finite() || percentage <= 0.0 {
+        return Err(anyhow::anyhow!(
+            "Invalid indexation percentage {percentage}, it must be positive"
+        ));
+    }
+
+    let contract = get_contract(db, id).await?;
+    if contract.cancel_date.is_some() {
+        return Err(anyhow::anyhow!("Contract {id} is cancelled, it can't be indexed"));
+    }
+    let old_monthly_rate = contract
+        .monthly_rate
+        .ok_or(anyhow::anyhow!("Contract {id} has no monthly rate"))?;
+    let new_monthly_rate = (old_monthly_rate as f64 * (100.0 + percentage) / 100.0).round() as i64;
+    let effective_date = chrono::Local::now().naive_local();
+    let source = source.as_str();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "UPDATE contracts SET monthly_rate = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        new_monthly_rate,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let rate_id = sqlx::query!(
+        r#"
+INSERT INTO contract_rates (
+    contract_id,
+    old_monthly_rate,
+    new_monthly_rate,
+    percentage,
+    source,
+    effective_date
+) VALUES (?, ?, ?, ?, ?, ?)
+"#,
+        id,
+        old_monthly_rate,
+        new_monthly_rate,
+        percentage,
+        source,
+        effective_date
+    )
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    let rate = sqlx::query_as!(
+        ContractRate,
+        r#"SELECT * FROM contract_rates WHERE id = ?"#,
+        rate_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(rate)
+}
+
+pub async fn get_clause(db: &SqlitePool, id: i64) -> Result<Clause> {
+    sqlx::query_as!(Clause, r#"SELECT * FROM clauses WHERE id = ?"#, id)
         .fetch_one(db)
         .await
         .map_err(anyhow::Error::msg)
 }
 
-pub async fn add_project(db: &SqlitePool, project: &ProjectCreateArgs) -> Result<i64> {
-    let project_id = sqlx::query!(
-        r#"
-INSERT INTO projects (
-    title,
-    description,
-    client_id
-) VALUES (?, ?, ?)
-"#,
-        project.title,
-        project.description,
-        project.client_id
+pub async fn get_clauses(db: &SqlitePool, category: Option<&str>) -> Result<Vec<Clause>> {
+    sqlx::query_as!(
+        Clause,
+        r#"SELECT * FROM clauses WHERE ($1 IS NULL OR category = $1) ORDER BY category, id"#,
+        category
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+pub async fn add_clause(db: &SqlitePool, clause: &ClauseCreateArgs) -> Result<i64> {
+    let clause_id = sqlx::query!(
+        r#"INSERT INTO clauses (category, title, description) VALUES (?, ?, ?)"#,
+        clause.category,
+        clause.title,
+        clause.description
     )
     .execute(db)
     .await?
     .last_insert_rowid();
 
-    Ok(project_id)
+    Ok(clause_id)
 }
 
-pub async fn update_project(db: &SqlitePool, id: i64, project: &ProjectUpdateArgs) -> Result<u64> {
+pub async fn update_clause(db: &SqlitePool, id: i64, clause: &ClauseUpdateArgs) -> Result<u64> {
     let result = sqlx::query!(
-        r#"UPDATE projects SET
+        r#"UPDATE clauses SET
+        category = COALESCE(?, category),
         title = COALESCE(?, title),
         description = COALESCE(?, description),
-        client_id = COALESCE(?, client_id)
+        updated_at = CURRENT_TIMESTAMP
         WHERE id = ?"#,
-        project.title,
-        project.description,
-        project.client_id,
+        clause.category,
+        clause.title,
+        clause.description,
         id
     )
     .execute(db)
@@ -457,130 +693,1484 @@ pub async fn update_project(db: &SqlitePool, id: i64, project: &ProjectUpdateArg
     Ok(result.rows_affected())
 }
 
-pub async fn get_project_task(db: &SqlitePool, id: i64) -> Result<ProjectTask> {
-    sqlx::query_as!(ProjectTask, r#"SELECT * FROM tasks WHERE id = ?"#, id)
+pub async fn get_contract_term(db: &SqlitePool, id: i64) -> Result<ContractTerm> {
+    sqlx::query_as!(ContractTerm, r#"SELECT * FROM contract_terms WHERE id = ?"#, id)
         .fetch_one(db)
         .await
         .map_err(anyhow::Error::msg)
 }
 
-pub async fn add_project_task(
+pub async fn get_contract_terms(db: &SqlitePool, contract_id: i64) -> Result<Vec<ContractTerm>> {
+    sqlx::query_as!(
+        ContractTerm,
+        r#"SELECT * FROM contract_terms WHERE contract_id = ? ORDER BY position"#,
+        contract_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+async fn count_contract_terms(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    contract_id: i64,
+) -> Result<i64> {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) FROM contract_terms WHERE contract_id = ?"#,
+        contract_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+/// Add a term at its position, the terms from there on move down one position
+pub async fn add_contract_term(
     db: &SqlitePool,
-    project_task: &ProjectTaskCreateArgs,
+    contract_id: i64,
+    term: &ContractTermCreateArgs,
 ) -> Result<i64> {
-    let result = sqlx::query!(
+    get_contract(db, contract_id).await?;
+
+    let (clause_id, category, title, description) = match term.clause_id {
+        Some(clause_id) => {
+            let clause = get_clause(db, clause_id).await?;
+            (Some(clause.id), clause.category, clause.title, clause.description)
+        }
+        None => (
+            None,
+            term.category.clone().unwrap_or("".to_string()),
+            term.title.clone().unwrap_or("".to_string()),
+            term.description.clone().unwrap_or("".to_string()),
+        ),
+    };
+
+    let mut tx = db.begin().await?;
+
+    let last_position = count_contract_terms(&mut tx, contract_id).await? + 1;
+    let position = term.position.unwrap_or(last_position).clamp(1, last_position);
+
+    sqlx::query!(
+        r#"UPDATE contract_terms SET position = position + 1 WHERE contract_id = ? AND position >= ?"#,
+        contract_id,
+        position
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let term_id = sqlx::query!(
         r#"
-INSERT INTO tasks (
-    project_id,
+INSERT INTO contract_terms (
+    contract_id,
+    clause_id,
+    position,
+    category,
     title,
-    description,
-    minutes_estimated,
-    minutes_spent,
-    minutes_remaining,
-    minutes_billed,
-    minute_rate
-) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+    description
+) VALUES (?, ?, ?, ?, ?, ?)
 "#,
-        project_task.project_id,
-        project_task.title,
-        project_task.description,
-        project_task.minutes_estimated,
-        project_task.minutes_spent,
-        project_task.minutes_remaining,
-        project_task.minutes_billed,
-        project_task.minute_rate
+        contract_id,
+        clause_id,
+        position,
+        category,
+        title,
+        description
     )
-    .execute(db)
-    .await?;
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
 
-    if result.rows_affected() == 0 {
-        return Err(anyhow::anyhow!("Failed to insert project task"));
-    }
+    tx.commit().await?;
 
-    Ok(result.last_insert_rowid())
+    Ok(term_id)
 }
 
-pub async fn update_project_task(
+/// Update a term, moving it to another position shifts the terms in between
+pub async fn update_contract_term(
     db: &SqlitePool,
     id: i64,
-    project_task: &ProjectTaskUpdateArgs,
+    term: &ContractTermUpdateArgs,
 ) -> Result<u64> {
+    let Some(old_term) = sqlx::query_as!(
+        ContractTerm,
+        r#"SELECT * FROM contract_terms WHERE id = ?"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(0);
+    };
+
+    let mut tx = db.begin().await?;
+
+    let position = match term.position {
+        Some(position) => {
+            let last_position = count_contract_terms(&mut tx, old_term.contract_id).await?;
+            let position = position.clamp(1, last_position);
+
+            sqlx::query!(
+                r#"UPDATE contract_terms SET position = position + 1
+                WHERE contract_id = $1 AND position >= $2 AND position < $3"#,
+                old_term.contract_id,
+                position,
+                old_term.position
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                r#"UPDATE contract_terms SET position = position - 1
+                WHERE contract_id = $1 AND position > $2 AND position <= $3"#,
+                old_term.contract_id,
+                old_term.position,
+                position
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            position
+        }
+        None => old_term.position,
+    };
+
     let result = sqlx::query!(
-        r#"UPDATE tasks SET
-        project_id = COALESCE(?, project_id),
+        r#"UPDATE contract_terms SET
+        position = ?,
+        category = COALESCE(?, category),
         title = COALESCE(?, title),
         description = COALESCE(?, description),
-        minutes_estimated = COALESCE(?, minutes_estimated),
-        minutes_spent = COALESCE(?, minutes_spent),
-        minutes_remaining = COALESCE(?, minutes_remaining),
-        minutes_billed = COALESCE(?, minutes_billed),
-        minute_rate = COALESCE(?, minute_rate)
+        updated_at = CURRENT_TIMESTAMP
         WHERE id = ?"#,
-        project_task.project_id,
-        project_task.title,
-        project_task.description,
-        project_task.minutes_estimated,
-        project_task.minutes_spent,
-        project_task.minutes_remaining,
-        project_task.minutes_billed,
-        project_task.minute_rate,
+        position,
+        term.category,
+        term.title,
+        term.description,
         id
     )
-    .execute(db)
+    .execute(&mut *tx)
     .await?;
 
+    tx.commit().await?;
+
     Ok(result.rows_affected())
 }
 
-pub async fn get_quote(db: &SqlitePool, id: i64) -> Result<Quote> {
-    sqlx::query_as!(Quote, r#"SELECT * FROM quotes WHERE id = ?"#, id)
-        .fetch_one(db)
-        .await
-        .map_err(anyhow::Error::msg)
+/// Remove a term, the terms after it move up one position
+pub async fn remove_contract_term(db: &SqlitePool, id: i64) -> Result<u64> {
+    let Some(term) = sqlx::query_as!(
+        ContractTerm,
+        r#"SELECT * FROM contract_terms WHERE id = ?"#,
+        id
+    )
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(0);
+    };
+
+    let mut tx = db.begin().await?;
+
+    let result = sqlx::query!(r#"DELETE FROM contract_terms WHERE id = ?"#, id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        r#"UPDATE contract_terms SET position = position - 1 WHERE contract_id = ? AND position > ?"#,
+        term.contract_id,
+        term.position
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
 }
 
-pub async fn add_quote(db: &SqlitePool, quote: &QuoteCreateArgs) -> Result<i64> {
-    let result = sqlx::query!(
-        r#"
-INSERT INTO quotes (
-    sender_id,
-    recipient_id,
-    project_id,
-    project_duration,
-    remarks,
-    total_before_vat,
-    discount,
-    vat_percentage,
-    currency,
-    total_after_vat,
-    quote_url
-) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-"#,
-        quote.sender_id,
-        quote.recipient_id,
-        quote.project_id,
-        quote.project_duration,
-        quote.remarks,
-        quote.total_before_vat,
-        quote.discount,
-        quote.vat_percentage,
-        quote.currency,
-        quote.total_after_vat,
-        quote.quote_url
+/// Render the contract pdf from its terms and store it as the contract_url
+pub async fn make_contract(db: &SqlitePool, id: i64) -> Result<String> {
+    let contract = get_contract(db, id).await?;
+    let terms = get_contract_terms(db, id).await?;
+    if terms.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Contract {id} has no terms, add them with account add-contract-term"
+        ));
+    }
+
+    let (sender_account, sender, sender_address) = get_sender(db, contract.sender_id).await?;
+    let (recipient, recipient_company_name, recipient_address) =
+        get_recipient(db, contract.recipient_id).await?;
+    let (sender_street, sender_postal_city) = address_lines(Some(&sender_address));
+    let (recipient_street, recipient_postal_city) = address_lines(recipient_address.as_ref());
+    let monthly_rate = contract.money(contract.monthly_rate.unwrap_or(0));
+    let format_date = |date: Option<chrono::NaiveDateTime>| {
+        date.map_or("".to_string(), |date| date.format("%d-%m-%Y").to_string())
+    };
+
+    let contract_template = self::contract::ContractTemplate {
+        sender_name: sender_account.name.clone().unwrap_or("".to_string()),
+        sender_company_name: sender.name.clone(),
+        sender_street,
+        sender_postal_city,
+        sender_phone: sender.phone.clone().unwrap_or("".to_string()),
+        sender_commerce_number: sender.commerce_number.clone().unwrap_or("".to_string()),
+        sender_vat_number: sender.vat_number.clone().unwrap_or("".to_string()),
+        sender_iban: sender.iban.clone().unwrap_or("".to_string()),
+        recipient_name: recipient.name.clone().unwrap_or("".to_string()),
+        recipient_company_name: recipient_company_name.unwrap_or("".to_string()),
+        recipient_street,
+        recipient_postal_city,
+        send_date: format_date(Some(contract.send_date.unwrap_or(contract.created_at))),
+        contract_number: contract.id.to_string(),
+        contract_type: contract.contract_type.clone().unwrap_or("".to_string()),
+        start_date: format_date(contract.start_date),
+        end_date: format_date(contract.end_date),
+        notice_period: contract.notice_period.clone().unwrap_or("".to_string()),
+        invoice_period_months: contract.invoice_period_months.unwrap_or(1).to_string(),
+        currency_symbol: monthly_rate.symbol(),
+        monthly_rate: monthly_rate.format_amount(),
+        terms: terms
+            .iter()
+            .map(|term| self::contract::ContractTermTableData {
+                article: term.position,
+                category: term.category.clone(),
+                title: term.title.clone(),
+                description: term.description.clone(),
+            })
+            .collect(),
+    };
+
+    let pdf_args = PdfArgs {
+        template: "contract".to_string(),
+        data: PdfData::Contract(&contract_template),
+    };
+    let contract_url = generate_pdf(&pdf_args).await?;
+
+    sqlx::query!(
+        r#"UPDATE contracts SET contract_url = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"#,
+        contract_url,
+        id
     )
     .execute(db)
     .await?;
 
-    if result.rows_affected() == 0 {
-        return Err(anyhow::anyhow!("Failed to insert quote"));
-    }
+    Ok(contract_url)
+}
 
-    Ok(result.last_insert_rowid())
+pub async fn get_project(db: &SqlitePool, id: i64) -> Result<Project> {
+    sqlx::query_as!(Project, r#"SELECT * FROM projects WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
 }
 
-pub async fn update_quote(db: &SqlitePool, id: i64, quote: &QuoteUpdateArgs) -> Result<u64> {
-    let result = sqlx::query!(
-        r#"UPDATE quotes SET
+// A project can't end or be due before it starts
+fn check_project_dates(
+    start_date: Option<chrono::NaiveDateTime>,
+    end_date: Option<chrono::NaiveDateTime>,
+    deadline: Option<chrono::NaiveDateTime>,
+) -> Result<()> {
+    let Some(start_date) = start_date else {
+        return Ok(());
+    };
+
+    for (name, date) in [("end date", end_date), ("deadline", deadline)] {
+        if let Some(date) = date.filter(|date| *date < start_date) {
+            return Err(anyhow::anyhow!(
+                "The {name} {} is before the start date {}",
+                date.format("%d-%m-%Y"),
+                start_date.format("%d-%m-%Y")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn add_project(db: &SqlitePool, project: &ProjectCreateArgs) -> Result<i64> {
+    check_project_dates(project.start_date, project.end_date, project.deadline)?;
+
+    // The dates are always inserted, so a project without them doesn't get the schema default
+    let project_id = sqlx::query!(
+        r#"
+INSERT INTO projects (
+    title,
+    description,
+    client_id,
+    start_date,
+    end_date,
+    deadline
+) VALUES (?, ?, ?, ?, ?, ?)
+"#,
+        project.title,
+        project.description,
+        project.client_id,
+        project.start_date,
+        project.end_date,
+        project.deadline
+    )
+    .execute(db)
+    .await?
+    .last_insert_rowid();
+
+    Ok(project_id)
+}
+
+pub async fn update_project(db: &SqlitePool, id: i64, project: &ProjectUpdateArgs) -> Result<u64> {
+    if let Some(current) = sqlx::query_as!(Project, r#"SELECT * FROM projects WHERE id = ?"#, id)
+        .fetch_optional(db)
+        .await?
+    {
+        check_project_dates(
+            project.start_date.or(current.start_date),
+            project.end_date.or(current.end_date),
+            project.deadline.or(current.deadline),
+        )?;
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE projects SET
+        title = COALESCE(?, title),
+        description = COALESCE(?, description),
+        client_id = COALESCE(?, client_id),
+        start_date = COALESCE(?, start_date),
+        end_date = COALESCE(?, end_date),
+        deadline = COALESCE(?, deadline)
+        WHERE id = ?"#,
+        project.title,
+        project.description,
+        project.client_id,
+        project.start_date,
+        project.end_date,
+        project.deadline,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn get_project_status_changes(
+    db: &SqlitePool,
+    project_id: i64,
+) -> Result<Vec<ProjectStatusChange>> {
+    sqlx::query_as!(
+        ProjectStatusChange,
+        r#"SELECT * FROM project_status_changes WHERE project_id = ? ORDER BY created_at, id"#,
+        project_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+// Set the status and record the transition in the given transaction
+async fn change_project_status(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    project_id: i64,
+    old_status: ProjectStatus,
+    new_status: ProjectStatus,
+    note: Option<&str>,
+) -> Result<()> {
+    let old_status_name = value_name(&old_status);
+    let old_status = old_status.as_str();
+    let new_status = new_status.as_str();
+
+    // Only moves the project on from the status it was read with, so a change made in the
+    // meantime isn't overwritten and logged with the wrong old status
+    let result = sqlx::query!(
+        r#"UPDATE projects SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? AND status = ?"#,
+        new_status,
+        project_id,
+        old_status
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::anyhow!(
+            "Project {project_id} is no longer {old_status_name}, its status was changed in the meantime"
+        ));
+    }
+
+    sqlx::query!(
+        r#"INSERT INTO project_status_changes (project_id, old_status, new_status, note) VALUES (?, ?, ?, ?)"#,
+        project_id,
+        old_status,
+        new_status,
+        note
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Move a project to one of the statuses `ProjectStatus::next` allows. A project is only finished
+/// when all its tasks are completed and only billed once it has an invoice
+pub async fn set_project_status(
+    db: &SqlitePool,
+    id: i64,
+    status: ProjectStatus,
+    note: Option<&str>,
+) -> Result<Project> {
+    let project = get_project(db, id).await?;
+    let old_status = project.status();
+
+    if !old_status.next().contains(&status) {
+        let allowed = match old_status.next() {
+            [] => "it can't change anymore".to_string(),
+            next => format!(
+                "it can go to {}",
+                next.iter().map(value_name).collect::<Vec<_>>().join(", ")
+            ),
+        };
+        return Err(anyhow::anyhow!(
+            "Project {id} can't go from {} to {}, {allowed}",
+            value_name(&old_status),
+            value_name(&status)
+        ));
+    }
+
+    match status {
+        ProjectStatus::Finished => {
+            let open_tasks = sqlx::query_scalar!(
+                r#"SELECT COUNT(*) FROM tasks WHERE project_id = ? AND NOT COALESCE(is_completed, FALSE)"#,
+                id
+            )
+            .fetch_one(db)
+            .await?;
+            if open_tasks > 0 {
+                return Err(anyhow::anyhow!(
+                    "Project {id} has {open_tasks} tasks that aren't completed"
+                ));
+            }
+        }
+        ProjectStatus::Billed => {
+            let invoices = sqlx::query_scalar!(
+                r#"SELECT COUNT(*) FROM invoices
+                WHERE project_id = $1
+                OR quote_id IN (SELECT id FROM quotes WHERE project_id = $1)"#,
+                id
+            )
+            .fetch_one(db)
+            .await?;
+            if invoices == 0 {
+                return Err(anyhow::anyhow!(
+                    "Project {id} has no invoice yet, make one with project make-invoice"
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    let mut tx = db.begin().await?;
+    change_project_status(&mut tx, id, old_status, status, note).await?;
+    tx.commit().await?;
+
+    get_project(db, id).await
+}
+
+pub async fn get_project_task(db: &SqlitePool, id: i64) -> Result<ProjectTask> {
+    sqlx::query_as!(ProjectTask, r#"SELECT * FROM tasks WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+pub async fn get_rate_card(db: &SqlitePool, id: i64) -> Result<RateCard> {
+    sqlx::query_as!(RateCard, r#"SELECT * FROM rate_cards WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+pub async fn get_rate_cards(
+    db: &SqlitePool,
+    company_id: Option<i64>,
+    client_id: Option<i64>,
+    role: Option<&str>,
+) -> Result<Vec<RateCard>> {
+    sqlx::query_as!(
+        RateCard,
+        r#"SELECT * FROM rate_cards
+        WHERE ($1 IS NULL OR company_id = $1)
+        AND ($2 IS NULL OR client_id = $2)
+        AND ($3 IS NULL OR role = $3)
+        ORDER BY company_id, client_id, role, valid_from"#,
+        company_id,
+        client_id,
+        role
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+fn check_rate_card_dates(
+    valid_from: Option<chrono::NaiveDateTime>,
+    valid_until: Option<chrono::NaiveDateTime>,
+) -> Result<()> {
+    match (valid_from, valid_until) {
+        (Some(valid_from), Some(valid_until)) if valid_until <= valid_from => Err(anyhow::anyhow!(
+            "A rate card valid until {} has to be valid from before that, not from {}",
+            valid_until.format("%d-%m-%Y"),
+            valid_from.format("%d-%m-%Y")
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub async fn add_rate_card(db: &SqlitePool, rate_card: &RateCardCreateArgs) -> Result<i64> {
+    check_rate_card_dates(rate_card.valid_from, rate_card.valid_until)?;
+    if let Some(company_id) = rate_card.company_id {
+        get_company(db, company_id).await?;
+    }
+    if let Some(client_id) = rate_card.client_id {
+        get_account(db, client_id).await?;
+    }
+
+    let rate_card_id = sqlx::query!(
+        r#"
+INSERT INTO rate_cards (
+    name,
+    company_id,
+    client_id,
+    role,
+    minute_rate,
+    valid_from,
+    valid_until
+) VALUES (?, ?, ?, ?, ?, ?, ?)
+"#,
+        rate_card.name,
+        rate_card.company_id,
+        rate_card.client_id,
+        rate_card.role,
+        rate_card.minute_rate,
+        rate_card.valid_from,
+        rate_card.valid_until
+    )
+    .execute(db)
+    .await?
+    .last_insert_rowid();
+
+    Ok(rate_card_id)
+}
+
+/// Update a rate card, tasks keep the minute rate they already got from it
+pub async fn update_rate_card(
+    db: &SqlitePool,
+    id: i64,
+    rate_card: &RateCardUpdateArgs,
+) -> Result<u64> {
+    let Some(old_rate_card) =
+        sqlx::query_as!(RateCard, r#"SELECT * FROM rate_cards WHERE id = ?"#, id)
+            .fetch_optional(db)
+            .await?
+    else {
+        return Ok(0);
+    };
+
+    check_rate_card_dates(
+        rate_card.valid_from.or(old_rate_card.valid_from),
+        rate_card.valid_until.or(old_rate_card.valid_until),
+    )?;
+    if let Some(company_id) = rate_card.company_id {
+        get_company(db, company_id).await?;
+    }
+    if let Some(client_id) = rate_card.client_id {
+        get_account(db, client_id).await?;
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE rate_cards SET
+        name = COALESCE(?, name),
+        company_id = COALESCE(?, company_id),
+        client_id = COALESCE(?, client_id),
+        role = COALESCE(?, role),
+        minute_rate = COALESCE(?, minute_rate),
+        valid_from = COALESCE(?, valid_from),
+        valid_until = COALESCE(?, valid_until),
+        updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?"#,
+        rate_card.name,
+        rate_card.company_id,
+        rate_card.client_id,
+        rate_card.role,
+        rate_card.minute_rate,
+        rate_card.valid_from,
+        rate_card.valid_until,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// The rate card that applies on a date, a card for the client beats one for the role, which
+/// beats one for the sender company. Of equally specific cards the one valid from the latest date
+/// wins
+pub async fn find_rate_card(
+    db: &SqlitePool,
+    company_id: Option<i64>,
+    client_id: i64,
+    role: Option<&str>,
+    date: chrono::NaiveDateTime,
+) -> Result<Option<RateCard>> {
+    sqlx::query_as!(
+        RateCard,
+        r#"SELECT * FROM rate_cards
+        WHERE (company_id IS NULL OR company_id = $1)
+        AND (client_id IS NULL OR client_id = $2)
+        AND (role IS NULL OR role = $3)
+        AND (valid_from IS NULL OR valid_from <= $4)
+        AND (valid_until IS NULL OR valid_until > $4)
+        ORDER BY (client_id IS NOT NULL) * 4 + (role IS NOT NULL) * 2 + (company_id IS NOT NULL) DESC,
+        valid_from DESC,
+        id DESC
+        LIMIT 1"#,
+        company_id,
+        client_id,
+        role,
+        date
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+// Hourly tasks without a minute rate of their own get it from the rate card of the sender that
+// applies now, instead of being billed at 0. The name of the rate card a rate comes from is shown
+// on the line of the task
+async fn apply_rate_cards(
+    db: &SqlitePool,
+    tasks: &mut [OrderedTask],
+    company_id: i64,
+    client_id: i64,
+) -> Result<()> {
+    let now = chrono::Local::now().naive_local();
+
+    for ordered_task in tasks.iter_mut() {
+        let task = &mut ordered_task.task;
+        if task.pricing() != TaskPricing::Hourly {
+            continue;
+        }
+
+        let rate_card = match task.minute_rate {
+            Some(_) => None,
+            None => {
+                let rate_card =
+                    find_rate_card(db, Some(company_id), client_id, task.role.as_deref(), now)
+                        .await?
+                        .ok_or(anyhow::anyhow!(
+                            "Task {} has no minute rate and no rate card applies to it",
+                            task.id
+                        ))?;
+                task.minute_rate = Some(rate_card.minute_rate);
+                task.rate_card_id = Some(rate_card.id);
+                Some(rate_card)
+            }
+        };
+        ordered_task.rate_card = rate_card.map(|rate_card| rate_card.name);
+    }
+
+    Ok(())
+}
+
+/// A task in the order it is listed and billed, subtasks follow their parent and belong to the
+/// milestone of their top level task
+#[derive(Debug, Serialize)]
+pub struct OrderedTask {
+    pub task: ProjectTask,
+    pub depth: usize,
+    pub milestone_id: Option<i64>,
+    pub milestone: Option<String>,
+    pub rate_card: Option<String>,
+}
+
+/// The tasks of a project grouped by milestone in the order of the milestones, the tasks without
+/// a milestone come last
+pub async fn get_ordered_project_tasks(db: &SqlitePool, project_id: i64) -> Result<Vec<OrderedTask>> {
+    let milestones = get_milestones(db, project_id).await?;
+    let tasks = sqlx::query_as!(
+        ProjectTask,
+        r#"SELECT * FROM tasks WHERE project_id = ? ORDER BY position, id"#,
+        project_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let milestone_of = |task: &ProjectTask| {
+        milestones
+            .iter()
+            .position(|milestone| Some(milestone.id) == task.milestone_id)
+    };
+    let mut roots = (0..tasks.len())
+        .filter(|i| {
+            tasks[*i]
+                .parent_id
+                .filter(|parent_id| tasks.iter().any(|task| task.id == *parent_id))
+                .is_none()
+        })
+        .collect::<Vec<_>>();
+    roots.sort_by_key(|i| milestone_of(&tasks[*i]).unwrap_or(milestones.len()));
+
+    let mut order = Vec::new();
+    let mut stack = roots
+        .into_iter()
+        .rev()
+        .map(|i| (i, 0, milestone_of(&tasks[i])))
+        .collect::<Vec<_>>();
+    while let Some((i, depth, milestone)) = stack.pop() {
+        order.push((i, depth, milestone));
+        let subtasks = (0..tasks.len()).filter(|subtask| tasks[*subtask].parent_id == Some(tasks[i].id));
+        stack.extend(subtasks.rev().map(|subtask| (subtask, depth + 1, milestone)));
+    }
+
+    let mut tasks = tasks.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(order
+        .into_iter()
+        .filter_map(|(i, depth, milestone)| {
+            let milestone = milestone.map(|milestone| &milestones[milestone]);
+            Some(OrderedTask {
+                task: tasks[i].take()?,
+                depth,
+                milestone_id: milestone.map(|milestone| milestone.id),
+                milestone: milestone.map(|milestone| milestone.title.clone()),
+                rate_card: None,
+            })
+        })
+        .collect())
+}
+
+async fn count_sibling_tasks(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    project_id: i64,
+    parent_id: Option<i64>,
+) -> Result<i64> {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) FROM tasks WHERE project_id = ? AND parent_id IS ?"#,
+        project_id,
+        parent_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+// The parent has to be a task of the same project that isn't the task itself or one of its subtasks
+async fn check_task_parent(
+    db: &SqlitePool,
+    project_id: i64,
+    task_id: Option<i64>,
+    parent_id: i64,
+) -> Result<()> {
+    let mut ancestor_id = Some(parent_id);
+    while let Some(id) = ancestor_id {
+        if Some(id) == task_id && id == parent_id {
+            return Err(anyhow::anyhow!("Task {id} can't be its own parent"));
+        }
+        if Some(id) == task_id {
+            return Err(anyhow::anyhow!(
+                "Task {parent_id} is a subtask of task {id} and can't be its parent"
+            ));
+        }
+
+        let ancestor = sqlx::query_as!(ProjectTask, r#"SELECT * FROM tasks WHERE id = ?"#, id)
+            .fetch_optional(db)
+            .await?
+            .ok_or(anyhow::anyhow!("Task {id} not found"))?;
+        if ancestor.project_id != project_id {
+            return Err(anyhow::anyhow!("Task {id} isn't a task of project {project_id}"));
+        }
+        ancestor_id = ancestor.parent_id;
+    }
+
+    Ok(())
+}
+
+async fn check_task_milestone(db: &SqlitePool, project_id: i64, milestone_id: i64) -> Result<()> {
+    let milestone = get_milestone(db, milestone_id).await?;
+    if milestone.project_id != project_id {
+        return Err(anyhow::anyhow!(
+            "Milestone {milestone_id} isn't a milestone of project {project_id}"
+        ));
+    }
+
+    Ok(())
+}
+
+// Fixed prices need a price, tasks priced per unit a price and the unit they are counted in
+fn check_task_pricing(pricing: TaskPricing, price: Option<i64>, unit: Option<&str>) -> Result<()> {
+    if pricing != TaskPricing::Hourly && price.is_none() {
+        return Err(anyhow::anyhow!(
+            "A task priced {} needs a price",
+            value_name(&pricing)
+        ));
+    }
+    if pricing == TaskPricing::PerUnit && unit.is_none() {
+        return Err(anyhow::anyhow!("A task priced per unit needs a unit, ie. \"page\""));
+    }
+
+    Ok(())
+}
+
+/// Add a task at its position among the tasks with the same parent, the tasks from there on move
+/// down one position
+pub async fn add_project_task(
+    db: &SqlitePool,
+    project_task: &ProjectTaskCreateArgs,
+) -> Result<i64> {
+    let pricing = project_task.pricing.unwrap_or(TaskPricing::Hourly);
+    check_task_pricing(pricing, project_task.price, project_task.unit.as_deref())?;
+
+    let pricing = pricing.as_str();
+
+    if let Some(parent_id) = project_task.parent_id {
+        check_task_parent(db, project_task.project_id, None, parent_id).await?;
+    }
+    if let Some(milestone_id) = project_task.milestone_id {
+        check_task_milestone(db, project_task.project_id, milestone_id).await?;
+    }
+
+    let mut tx = db.begin().await?;
+
+    let last_position =
+        count_sibling_tasks(&mut tx, project_task.project_id, project_task.parent_id).await? + 1;
+    let position = project_task.position.unwrap_or(last_position).clamp(1, last_position);
+
+    sqlx::query!(
+        r#"UPDATE tasks SET position = position + 1
+        WHERE project_id = ? AND parent_id IS ? AND position >= ?"#,
+        project_task.project_id,
+        project_task.parent_id,
+        position
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let result = sqlx::query!(
+        r#"
+INSERT INTO tasks (
+    project_id,
+    title,
+    description,
+    minutes_estimated,
+    minutes_spent,
+    minutes_remaining,
+    minutes_billed,
+    minute_rate,
+    vat_percentage,
+    parent_id,
+    milestone_id,
+    position,
+    pricing,
+    price,
+    quantity,
+    unit,
+    role
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#,
+        project_task.project_id,
+        project_task.title,
+        project_task.description,
+        project_task.minutes_estimated,
+        project_task.minutes_spent,
+        project_task.minutes_remaining,
+        project_task.minutes_billed,
+        project_task.minute_rate,
+        project_task.vat_percentage,
+        project_task.parent_id,
+        project_task.milestone_id,
+        position,
+        pricing,
+        project_task.price,
+        project_task.quantity,
+        project_task.unit,
+        project_task.role
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::anyhow!("Failed to insert project task"));
+    }
+
+    tx.commit().await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+/// Update a task, moving it to another parent or position shifts the tasks around it
+pub async fn update_project_task(
+    db: &SqlitePool,
+    id: i64,
+    project_task: &ProjectTaskUpdateArgs,
+) -> Result<u64> {
+    let Some(old_task) = sqlx::query_as!(ProjectTask, r#"SELECT * FROM tasks WHERE id = ?"#, id)
+        .fetch_optional(db)
+        .await?
+    else {
+        return Ok(0);
+    };
+
+    // 0 clears the parent or milestone, a task moved to another project leaves both behind
+    let project_id = project_task.project_id.unwrap_or(old_task.project_id);
+    let moved_project = project_id != old_task.project_id;
+    let parent_id = match project_task.parent_id {
+        Some(0) => None,
+        Some(parent_id) => Some(parent_id),
+        None if moved_project => None,
+        None => old_task.parent_id,
+    };
+    let milestone_id = match project_task.milestone_id {
+        Some(0) => None,
+        Some(milestone_id) => Some(milestone_id),
+        None if moved_project || parent_id.is_some() => None,
+        None => old_task.milestone_id,
+    };
+
+    let pricing = project_task.pricing.unwrap_or(old_task.pricing());
+    check_task_pricing(
+        pricing,
+        project_task.price.or(old_task.price),
+        project_task.unit.as_deref().or(old_task.unit.as_deref()),
+    )?;
+    let pricing = pricing.as_str();
+
+    if moved_project {
+        let subtasks =
+            sqlx::query_scalar!(r#"SELECT COUNT(*) FROM tasks WHERE parent_id = ?"#, id)
+                .fetch_one(db)
+                .await?;
+        if subtasks > 0 {
+            return Err(anyhow::anyhow!(
+                "Task {id} has subtasks and can't be moved to another project"
+            ));
+        }
+    }
+    if let Some(parent_id) = parent_id {
+        check_task_parent(db, project_id, Some(id), parent_id).await?;
+    }
+    if let Some(milestone_id) = milestone_id {
+        if parent_id.is_some() {
+            return Err(anyhow::anyhow!(
+                "Task {id} is a subtask and belongs to the milestone of its top level task"
+            ));
+        }
+        check_task_milestone(db, project_id, milestone_id).await?;
+    }
+
+    let mut tx = db.begin().await?;
+
+    let position = if !moved_project && parent_id == old_task.parent_id {
+        match project_task.position {
+            Some(position) => {
+                let last_position = count_sibling_tasks(&mut tx, project_id, parent_id).await?;
+                let position = position.clamp(1, last_position);
+
+                sqlx::query!(
+                    r#"UPDATE tasks SET position = position + 1
+                    WHERE project_id = $1 AND parent_id IS $2 AND position >= $3 AND position < $4"#,
+                    project_id,
+                    parent_id,
+                    position,
+                    old_task.position
+                )
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query!(
+                    r#"UPDATE tasks SET position = position - 1
+                    WHERE project_id = $1 AND parent_id IS $2 AND position > $3 AND position <= $4"#,
+                    project_id,
+                    parent_id,
+                    old_task.position,
+                    position
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                position
+            }
+            None => old_task.position,
+        }
+    } else {
+        sqlx::query!(
+            r#"UPDATE tasks SET position = position - 1
+            WHERE project_id = ? AND parent_id IS ? AND position > ?"#,
+            old_task.project_id,
+            old_task.parent_id,
+            old_task.position
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let last_position = count_sibling_tasks(&mut tx, project_id, parent_id).await? + 1;
+        let position = project_task.position.unwrap_or(last_position).clamp(1, last_position);
+        sqlx::query!(
+            r#"UPDATE tasks SET position = position + 1
+            WHERE project_id = ? AND parent_id IS ? AND position >= ?"#,
+            project_id,
+            parent_id,
+            position
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        position
+    };
+
+    let result = sqlx::query!(
+        r#"UPDATE tasks SET
+        project_id = ?,
+        title = COALESCE(?, title),
+        description = COALESCE(?, description),
+        minutes_estimated = COALESCE(?, minutes_estimated),
+        minutes_spent = COALESCE(?, minutes_spent),
+        minutes_remaining = COALESCE(?, minutes_remaining),
+        minutes_billed = COALESCE(?, minutes_billed),
+        minute_rate = COALESCE(?, minute_rate),
+        vat_percentage = COALESCE(?, vat_percentage),
+        parent_id = ?,
+        milestone_id = ?,
+        position = ?,
+        pricing = ?,
+        price = COALESCE(?, price),
+        quantity = COALESCE(?, quantity),
+        unit = COALESCE(?, unit),
+        role = COALESCE(?, role)
+        WHERE id = ?"#,
+        project_id,
+        project_task.title,
+        project_task.description,
+        project_task.minutes_estimated,
+        project_task.minutes_spent,
+        project_task.minutes_remaining,
+        project_task.minutes_billed,
+        project_task.minute_rate,
+        project_task.vat_percentage,
+        parent_id,
+        milestone_id,
+        position,
+        pricing,
+        project_task.price,
+        project_task.quantity,
+        project_task.unit,
+        project_task.role,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Remove a task and its subtasks, the tasks after it move up one position
+pub async fn remove_project_task(db: &SqlitePool, id: i64) -> Result<u64> {
+    let Some(task) = sqlx::query_as!(ProjectTask, r#"SELECT * FROM tasks WHERE id = ?"#, id)
+        .fetch_optional(db)
+        .await?
+    else {
+        return Ok(0);
+    };
+
+    let mut tx = db.begin().await?;
+
+    let result = sqlx::query!(r#"DELETE FROM tasks WHERE id = ?"#, id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        r#"UPDATE tasks SET position = position - 1
+        WHERE project_id = ? AND parent_id IS ? AND position > ?"#,
+        task.project_id,
+        task.parent_id,
+        task.position
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn get_milestone(db: &SqlitePool, id: i64) -> Result<Milestone> {
+    sqlx::query_as!(Milestone, r#"SELECT * FROM milestones WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+pub async fn get_milestones(db: &SqlitePool, project_id: i64) -> Result<Vec<Milestone>> {
+    sqlx::query_as!(
+        Milestone,
+        r#"SELECT * FROM milestones WHERE project_id = ? ORDER BY position"#,
+        project_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+async fn count_milestones(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    project_id: i64,
+) -> Result<i64> {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) FROM milestones WHERE project_id = ?"#,
+        project_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+/// Add a milestone at its position, the milestones from there on move down one position
+pub async fn add_milestone(db: &SqlitePool, milestone: &MilestoneCreateArgs) -> Result<i64> {
+    get_project(db, milestone.project_id).await?;
+
+    let mut tx = db.begin().await?;
+
+    let last_position = count_milestones(&mut tx, milestone.project_id).await? + 1;
+    let position = milestone.position.unwrap_or(last_position).clamp(1, last_position);
+
+    sqlx::query!(
+        r#"UPDATE milestones SET position = position + 1 WHERE project_id = ? AND position >= ?"#,
+        milestone.project_id,
+        position
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let milestone_id = sqlx::query!(
+        r#"
+INSERT INTO milestones (
+    project_id,
+    position,
+    title,
+    description,
+    due_date
+) VALUES (?, ?, ?, ?, ?)
+"#,
+        milestone.project_id,
+        position,
+        milestone.title,
+        milestone.description,
+        milestone.due_date
+    )
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    tx.commit().await?;
+
+    Ok(milestone_id)
+}
+
+/// Update a milestone, moving it to another position shifts the milestones in between
+pub async fn update_milestone(
+    db: &SqlitePool,
+    id: i64,
+    milestone: &MilestoneUpdateArgs,
+) -> Result<u64> {
+    let Some(old_milestone) =
+        sqlx::query_as!(Milestone, r#"SELECT * FROM milestones WHERE id = ?"#, id)
+            .fetch_optional(db)
+            .await?
+    else {
+        return Ok(0);
+    };
+
+    let mut tx = db.begin().await?;
+
+    let position = match milestone.position {
+        Some(position) => {
+            let last_position = count_milestones(&mut tx, old_milestone.project_id).await?;
+            let position = position.clamp(1, last_position);
+
+            sqlx::query!(
+                r#"UPDATE milestones SET position = position + 1
+                WHERE project_id = $1 AND position >= $2 AND position < $3"#,
+                old_milestone.project_id,
+                position,
+                old_milestone.position
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                r#"UPDATE milestones SET position = position - 1
+                WHERE project_id = $1 AND position > $2 AND position <= $3"#,
+                old_milestone.project_id,
+                old_milestone.position,
+                position
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            position
+        }
+        None => old_milestone.position,
+    };
+
+    let result = sqlx::query!(
+        r#"UPDATE milestones SET
+        position = ?,
+        title = COALESCE(?, title),
+        description = COALESCE(?, description),
+        due_date = COALESCE(?, due_date),
+        updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?"#,
+        position,
+        milestone.title,
+        milestone.description,
+        milestone.due_date,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Remove a milestone, its tasks keep existing without a milestone and the milestones after it
+/// move up one position
+pub async fn remove_milestone(db: &SqlitePool, id: i64) -> Result<u64> {
+    let Some(milestone) =
+        sqlx::query_as!(Milestone, r#"SELECT * FROM milestones WHERE id = ?"#, id)
+            .fetch_optional(db)
+            .await?
+    else {
+        return Ok(0);
+    };
+
+    let mut tx = db.begin().await?;
+
+    let result = sqlx::query!(r#"DELETE FROM milestones WHERE id = ?"#, id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        r#"UPDATE milestones SET position = position - 1 WHERE project_id = ? AND position > ?"#,
+        milestone.project_id,
+        milestone.position
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn get_time_entry(db: &SqlitePool, id: i64) -> Result<TimeEntry> {
+    sqlx::query_as!(TimeEntry, r#"SELECT * FROM time_entries WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+pub async fn get_time_entries(db: &SqlitePool, task_id: Option<i64>) -> Result<Vec<TimeEntry>> {
+    sqlx::query_as!(
+        TimeEntry,
+        r#"SELECT * FROM time_entries WHERE ($1 IS NULL OR task_id = $1) ORDER BY start_date"#,
+        task_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+pub async fn get_running_timer(db: &SqlitePool) -> Result<Option<TimeEntry>> {
+    sqlx::query_as!(TimeEntry, r#"SELECT * FROM time_entries WHERE end_date IS NULL"#)
+        .fetch_optional(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+// Time logged on a task adds to minutes_spent and, when the task is estimated, lowers
+// minutes_remaining
+async fn add_task_minutes(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    task_id: i64,
+    minutes: i64,
+) -> Result<()> {
+    sqlx::query!(
+        r#"UPDATE tasks SET
+        minutes_spent = COALESCE(minutes_spent, 0) + $1,
+        minutes_remaining = CASE
+            WHEN minutes_estimated IS NULL THEN minutes_remaining
+            ELSE MAX(minutes_estimated - (COALESCE(minutes_spent, 0) + $1), 0)
+        END
+        WHERE id = $2"#,
+        minutes,
+        task_id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn start_timer(db: &SqlitePool, task_id: i64, note: Option<&str>) -> Result<TimeEntry> {
+    let task = get_project_task(db, task_id).await?;
+
+    if let Some(timer) = get_running_timer(db).await? {
+        return Err(anyhow::anyhow!(
+            "The timer of task {} is running since {}, stop it first",
+            timer.task_id,
+            timer.start_date.format("%d-%m-%Y %H:%M")
+        ));
+    }
+
+    let start_date = chrono::Local::now().naive_local();
+    let result = sqlx::query!(
+        r#"INSERT INTO time_entries (task_id, start_date, note) VALUES (?, ?, ?)"#,
+        task.id,
+        start_date,
+        note
+    )
+    .execute(db)
+    .await?;
+
+    get_time_entry(db, result.last_insert_rowid()).await
+}
+
+/// Stop the running timer and add the minutes to its task, rounded to the nearest minute
+pub async fn stop_timer(db: &SqlitePool) -> Result<TimeEntry> {
+    let mut tx = db.begin().await?;
+    let timer = sqlx::query_as!(TimeEntry, r#"SELECT * FROM time_entries WHERE end_date IS NULL"#)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(anyhow::anyhow!("No timer is running"))?;
+
+    let end_date = chrono::Local::now().naive_local();
+    let minutes = (end_date.signed_duration_since(timer.start_date).num_seconds() + 30) / 60;
+
+    // A timer that was stopped in the meantime doesn't add its minutes twice
+    let result = sqlx::query!(
+        r#"UPDATE time_entries SET
+        end_date = ?,
+        minutes = ?,
+        updated_at = CURRENT_TIMESTAMP
+        WHERE id = ? AND end_date IS NULL"#,
+        end_date,
+        minutes,
+        timer.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() != 1 {
+        return Err(anyhow::anyhow!("Timer {} is stopped already", timer.id));
+    }
+
+    add_task_minutes(&mut tx, timer.task_id, minutes).await?;
+    tx.commit().await?;
+
+    get_time_entry(db, timer.id).await
+}
+
+pub async fn log_time(
+    db: &SqlitePool,
+    task_id: i64,
+    time_entry: &TimeEntryCreateArgs,
+) -> Result<TimeEntry> {
+    let task = get_project_task(db, task_id).await?;
+    let minutes = time_entry.duration.0;
+    let start_date = time_entry
+        .date
+        .unwrap_or(chrono::Local::now().naive_local());
+    let end_date = start_date + chrono::Duration::minutes(minutes);
+
+    let mut tx = db.begin().await?;
+    let result = sqlx::query!(
+        r#"INSERT INTO time_entries (task_id, start_date, end_date, minutes, note) VALUES (?, ?, ?, ?, ?)"#,
+        task.id,
+        start_date,
+        end_date,
+        minutes,
+        time_entry.note
+    )
+    .execute(&mut *tx)
+    .await?;
+    add_task_minutes(&mut tx, task.id, minutes).await?;
+    tx.commit().await?;
+
+    get_time_entry(db, result.last_insert_rowid()).await
+}
+
+pub async fn get_quote(db: &SqlitePool, id: i64) -> Result<Quote> {
+    sqlx::query_as!(Quote, r#"SELECT * FROM quotes WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+pub async fn add_quote(db: &SqlitePool, quote: &QuoteCreateArgs) -> Result<i64> {
+    let expire_date = match quote.expire_date {
+        Some(expire_date) => expire_date,
+        None => default_expire_date(None)?,
+    };
+
+    // A quote of a project is its next revision, like a made quote
+    let mut tx = db.begin().await?;
+    let (revision, current_quote) = next_quote_revision(&mut tx, quote.project_id).await?;
+
+    let result = sqlx::query!(
+        r#"
+INSERT INTO quotes (
+    sender_id,
+    recipient_id,
+    project_id,
+    project_duration,
+    remarks,
+    total_before_vat,
+    discount,
+    vat_percentage,
+    currency,
+    total_after_vat,
+    quote_url,
+    expire_date,
+    reverse_charge,
+    revision
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#,
+        quote.sender_id,
+        quote.recipient_id,
+        quote.project_id,
+        quote.project_duration,
+        quote.remarks,
+        quote.total_before_vat,
+        quote.discount,
+        quote.vat_percentage,
+        quote.currency,
+        quote.total_after_vat,
+        quote.quote_url,
+        expire_date,
+        quote.reverse_charge,
+        revision
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::anyhow!("Failed to insert quote"));
+    }
+
+    let quote_id = result.last_insert_rowid();
+    supersede_quote(&mut tx, current_quote.as_ref(), quote_id).await?;
+    tx.commit().await?;
+
+    Ok(quote_id)
+}
+
+pub async fn update_quote(db: &SqlitePool, id: i64, quote: &QuoteUpdateArgs) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"UPDATE quotes SET
         sender_id = COALESCE(?, sender_id),
         recipient_id = COALESCE(?, recipient_id),
         project_id = COALESCE(?, project_id),
@@ -591,7 +2181,9 @@ pub async fn update_quote(db: &SqlitePool, id: i64, quote: &QuoteUpdateArgs) ->
         vat_percentage = COALESCE(?, vat_percentage),
         currency = COALESCE(?, currency),
         total_after_vat = COALESCE(?, total_after_vat),
-        quote_url = COALESCE(?, quote_url)
+        quote_url = COALESCE(?, quote_url),
+        expire_date = COALESCE(?, expire_date),
+        reverse_charge = COALESCE(?, reverse_charge)
         WHERE id = ?"#,
         quote.sender_id,
         quote.recipient_id,
@@ -604,6 +2196,8 @@ pub async fn update_quote(db: &SqlitePool, id: i64, quote: &QuoteUpdateArgs) ->
         quote.currency,
         quote.total_after_vat,
         quote.quote_url,
+        quote.expire_date,
+        quote.reverse_charge,
         id
     )
     .execute(db)
@@ -612,6 +2206,18 @@ pub async fn update_quote(db: &SqlitePool, id: i64, quote: &QuoteUpdateArgs) ->
     Ok(result.rows_affected())
 }
 
+mod vat_table {
+    use serde::Serialize;
+    use struct_field_names_as_array::FieldNamesAsArray;
+
+    #[derive(Serialize, FieldNamesAsArray)]
+    pub struct VatTableData {
+        pub vat_percentage: String,
+        pub base: String,
+        pub vat: String,
+    }
+}
+
 mod quote {
     use serde::Serialize;
     use simple_pdf_generator_derive::PdfTemplate;
@@ -621,8 +2227,10 @@ mod quote {
     pub struct QuoteTableData {
         pub title: String,
         pub description: String,
-        pub hours_estimated: f64,
-        pub hourly_rate: String,
+        pub quantity: String,
+        pub unit: String,
+        pub unit_price: String,
+        pub rate_card: String,
         pub total: String,
     }
 
@@ -633,43 +2241,139 @@ mod quote {
         pub sender_postal_city: String,
         pub sender_phone: String,
         pub sender_email: String,
+        pub sender_company_name: String,
         pub sender_commerce_number: String,
+        pub sender_vat_number: String,
+        pub sender_iban: String,
         pub recipient_name: String,
         pub recipient_company_name: String,
         pub recipient_street: String,
         pub recipient_postal_city: String,
         pub send_date: String,
+        pub quote_id: String,
+        pub revision: String,
         pub project_title: String,
         pub project_description: String,
         pub project_duration: String,
         #[PdfTableData]
-        pub project_tasks: Vec<QuoteTableData>,
+        pub project_tasks: Vec<QuoteTableData>,
+        pub remarks: String,
+        pub currency_symbol: String,
+        pub total_before_vat: String,
+        pub discount: String,
+        #[PdfTableData]
+        pub vat_lines: Vec<super::vat_table::VatTableData>,
+        pub vat_note: String,
+        pub vat_amount: String,
+        pub total_after_vat: String,
+    }
+}
+
+mod invoice {
+    use serde::Serialize;
+    use simple_pdf_generator_derive::PdfTemplate;
+    use struct_field_names_as_array::FieldNamesAsArray;
+
+    #[derive(Serialize, FieldNamesAsArray)]
+    pub struct InvoiceTableData {
+        pub title: String,
+        pub description: String,
+        pub quantity: String,
+        pub unit: String,
+        pub unit_price: String,
+        pub total: String,
+    }
+
+    #[derive(PdfTemplate, FieldNamesAsArray)]
+    pub struct InvoiceTemplate {
+        pub sender_name: String,
+        pub sender_company_name: String,
+        pub sender_street: String,
+        pub sender_postal_city: String,
+        pub sender_phone: String,
+        pub sender_commerce_number: String,
+        pub sender_vat_number: String,
+        pub sender_iban: String,
+        pub recipient_name: String,
+        pub recipient_company_name: String,
+        pub recipient_street: String,
+        pub recipient_postal_city: String,
+        pub send_date: String,
+        pub project_title: String,
+        pub invoice_number: String,
+        pub due_date: String,
+        #[PdfTableData]
+        pub project_tasks: Vec<InvoiceTableData>,
+        pub remarks: String,
+        pub currency_symbol: String,
+        pub total_before_vat: String,
+        pub discount: String,
+        #[PdfTableData]
+        pub vat_lines: Vec<super::vat_table::VatTableData>,
+        pub vat_note: String,
+        pub vat_amount: String,
+        pub total_after_vat: String,
+    }
+}
+
+mod invoice_maintenance {
+    use serde::Serialize;
+    use simple_pdf_generator_derive::PdfTemplate;
+    use struct_field_names_as_array::FieldNamesAsArray;
+
+    #[derive(Serialize, FieldNamesAsArray)]
+    pub struct InvoiceMaintenanceTableData {
+        pub description: String,
+        pub months: String,
+        pub monthly_rate: String,
+        pub total: String,
+    }
+
+    #[derive(PdfTemplate, FieldNamesAsArray)]
+    pub struct InvoiceMaintenanceTemplate {
+        pub sender_name: String,
+        pub sender_company_name: String,
+        pub sender_street: String,
+        pub sender_postal_city: String,
+        pub sender_phone: String,
+        pub sender_commerce_number: String,
+        pub sender_vat_number: String,
+        pub sender_iban: String,
+        pub recipient_name: String,
+        pub recipient_company_name: String,
+        pub recipient_street: String,
+        pub recipient_postal_city: String,
+        pub send_date: String,
+        pub contract_type: String,
+        pub invoice_number: String,
+        pub due_date: String,
+        #[PdfTableData]
+        pub invoice_table: Vec<InvoiceMaintenanceTableData>,
         pub remarks: String,
         pub currency_symbol: String,
         pub total_before_vat: String,
         pub discount: String,
-        pub vat_percentage: String,
+        #[PdfTableData]
+        pub vat_lines: Vec<super::vat_table::VatTableData>,
+        pub vat_note: String,
         pub vat_amount: String,
         pub total_after_vat: String,
     }
 }
 
-mod invoice {
+mod credit_note {
     use serde::Serialize;
     use simple_pdf_generator_derive::PdfTemplate;
     use struct_field_names_as_array::FieldNamesAsArray;
 
     #[derive(Serialize, FieldNamesAsArray)]
-    pub struct InvoiceTableData {
-        pub title: String,
+    pub struct CreditNoteTableData {
         pub description: String,
-        pub hours_spent: f64,
-        pub hourly_rate: String,
         pub total: String,
     }
 
     #[derive(PdfTemplate, FieldNamesAsArray)]
-    pub struct InvoiceTemplate {
+    pub struct CreditNoteTemplate {
         pub sender_name: String,
         pub sender_company_name: String,
         pub sender_street: String,
@@ -677,41 +2381,43 @@ mod invoice {
         pub sender_phone: String,
         pub sender_commerce_number: String,
         pub sender_vat_number: String,
+        pub sender_iban: String,
         pub recipient_name: String,
         pub recipient_company_name: String,
         pub recipient_street: String,
         pub recipient_postal_city: String,
         pub send_date: String,
-        pub project_title: String,
+        pub credit_note_number: String,
         pub invoice_number: String,
-        pub due_date: String,
+        pub invoice_date: String,
         #[PdfTableData]
-        pub project_tasks: Vec<InvoiceTableData>,
+        pub credit_lines: Vec<CreditNoteTableData>,
         pub remarks: String,
         pub currency_symbol: String,
         pub total_before_vat: String,
-        pub discount: String,
-        pub vat_percentage: String,
+        #[PdfTableData]
+        pub vat_lines: Vec<super::vat_table::VatTableData>,
+        pub vat_note: String,
         pub vat_amount: String,
         pub total_after_vat: String,
     }
 }
 
-mod invoice_maintenance {
+mod contract {
     use serde::Serialize;
     use simple_pdf_generator_derive::PdfTemplate;
     use struct_field_names_as_array::FieldNamesAsArray;
 
     #[derive(Serialize, FieldNamesAsArray)]
-    pub struct InvoiceMaintenanceTableData {
+    pub struct ContractTermTableData {
+        pub article: i64,
+        pub category: String,
+        pub title: String,
         pub description: String,
-        pub months: i64,
-        pub monthly_rate: String,
-        pub total: String,
     }
 
     #[derive(PdfTemplate, FieldNamesAsArray)]
-    pub struct InvoiceMaintenanceTemplate {
+    pub struct ContractTemplate {
         pub sender_name: String,
         pub sender_company_name: String,
         pub sender_street: String,
@@ -719,23 +2425,22 @@ mod invoice_maintenance {
         pub sender_phone: String,
         pub sender_commerce_number: String,
         pub sender_vat_number: String,
+        pub sender_iban: String,
         pub recipient_name: String,
         pub recipient_company_name: String,
         pub recipient_street: String,
         pub recipient_postal_city: String,
         pub send_date: String,
+        pub contract_number: String,
         pub contract_type: String,
-        pub invoice_number: String,
-        pub due_date: String,
-        #[PdfTableData]
-        pub invoice_table: Vec<InvoiceMaintenanceTableData>,
-        pub remarks: String,
+        pub start_date: String,
+        pub end_date: String,
+        pub notice_period: String,
+        pub invoice_period_months: String,
         pub currency_symbol: String,
-        pub total_before_vat: String,
-        pub discount: String,
-        pub vat_percentage: String,
-        pub vat_amount: String,
-        pub total_after_vat: String,
+        pub monthly_rate: String,
+        #[PdfTableData]
+        pub terms: Vec<ContractTermTableData>,
     }
 }
 
@@ -743,6 +2448,8 @@ enum PdfData<'a> {
     Quote(&'a self::quote::QuoteTemplate),
     Invoice(&'a self::invoice::InvoiceTemplate),
     InvoiceMaintenance(&'a self::invoice_maintenance::InvoiceMaintenanceTemplate),
+    CreditNote(&'a self::credit_note::CreditNoteTemplate),
+    Contract(&'a self::contract::ContractTemplate),
 }
 
 struct PdfArgs<'a> {
@@ -823,6 +2530,8 @@ impl PdfData<'_> {
             PdfData::Quote(_) => "quote".to_string(),
             PdfData::Invoice(_) => "invoice".to_string(),
             PdfData::InvoiceMaintenance(_) => "invoice_maintenance".to_string(),
+            PdfData::CreditNote(_) => "credit_note".to_string(),
+            PdfData::Contract(_) => "contract".to_string(),
         }
     }
 
@@ -842,6 +2551,12 @@ impl PdfData<'_> {
                     .iter()
                     .fold("".to_string(), |acc, name| format!("{acc}%%{name}%%\n"))
             }
+            PdfData::CreditNote(_) => self::credit_note::CreditNoteTemplate::FIELD_NAMES_AS_ARRAY
+                .iter()
+                .fold("".to_string(), |acc, name| format!("{acc}%%{name}%%\n")),
+            PdfData::Contract(_) => self::contract::ContractTemplate::FIELD_NAMES_AS_ARRAY
+                .iter()
+                .fold("".to_string(), |acc, name| format!("{acc}%%{name}%%\n")),
         }
     }
 
@@ -862,8 +2577,8 @@ impl PdfData<'_> {
                 let pdf_path = format!(
                     "{}/offerte-{}-{}.pdf",
                     output_dir.to_path_buf().display(),
-                    quote_template.project_title,
-                    quote_template.send_date
+                    quote_template.quote_id,
+                    quote_template.revision
                 );
                 tokio::fs::write(&pdf_path, pdf_buf)
                     .await
@@ -876,10 +2591,9 @@ impl PdfData<'_> {
                     .await
                     .map_err(|e| anyhow::anyhow!("Failed to generate pdf: {}", e))?;
                 let pdf_path = format!(
-                    "{}/factuur-{}-{}.pdf",
+                    "{}/factuur-{}.pdf",
                     output_dir.to_path_buf().display(),
-                    invoice_template.project_title,
-                    invoice_template.send_date
+                    file_name_part(&invoice_template.invoice_number)
                 );
                 tokio::fs::write(&pdf_path, pdf_buf)
                     .await
@@ -892,10 +2606,40 @@ impl PdfData<'_> {
                     .await
                     .map_err(|e| anyhow::anyhow!("Failed to generate pdf: {}", e))?;
                 let pdf_path = format!(
-                    "{}/factuur-{}-{}.pdf",
+                    "{}/factuur-{}.pdf",
+                    output_dir.to_path_buf().display(),
+                    file_name_part(&invoice_maintenance_template.invoice_number)
+                );
+                tokio::fs::write(&pdf_path, pdf_buf)
+                    .await
+                    .expect("Failed to write pdf file");
+                Ok(pdf_path)
+            }
+            PdfData::CreditNote(credit_note_template) => {
+                let pdf_buf = credit_note_template
+                    .generate_pdf(html_path, assets, print_options)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to generate pdf: {}", e))?;
+                let pdf_path = format!(
+                    "{}/creditnota-{}.pdf",
+                    output_dir.to_path_buf().display(),
+                    file_name_part(&credit_note_template.credit_note_number)
+                );
+                tokio::fs::write(&pdf_path, pdf_buf)
+                    .await
+                    .expect("Failed to write pdf file");
+                Ok(pdf_path)
+            }
+            PdfData::Contract(contract_template) => {
+                let pdf_buf = contract_template
+                    .generate_pdf(html_path, assets, print_options)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to generate pdf: {}", e))?;
+                let pdf_path = format!(
+                    "{}/contract-{}-{}.pdf",
                     output_dir.to_path_buf().display(),
-                    invoice_maintenance_template.contract_type,
-                    invoice_maintenance_template.send_date
+                    contract_template.contract_number,
+                    contract_template.send_date
                 );
                 tokio::fs::write(&pdf_path, pdf_buf)
                     .await
@@ -906,6 +2650,11 @@ impl PdfData<'_> {
     }
 }
 
+// Document numbers name their pdf, a number format like "INV/{YYYY}" can't add directories
+fn file_name_part(value: &str) -> String {
+    value.replace(['/', '\\'], "-")
+}
+
 // Generate pdf with simple_pdf_generator
 async fn generate_pdf(pdf_args: &PdfArgs<'_>) -> Result<String> {
     let template_dir = get_env_or_home_dir!("CCLI_TEMPLATE_DIR", "templates");
@@ -949,6 +2698,417 @@ async fn generate_pdf(pdf_args: &PdfArgs<'_>) -> Result<String> {
         .await
 }
 
+// The account quotes and project invoices are sent from when no sender is given
+fn default_sender_id() -> Result<i64> {
+    let sender_id = std::env::var("CCLI_DEFAULT_SENDER_ID").map_err(|_| {
+        anyhow::anyhow!("No sender given, use --sender-id or set CCLI_DEFAULT_SENDER_ID")
+    })?;
+
+    sender_id
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| anyhow::anyhow!("CCLI_DEFAULT_SENDER_ID \"{sender_id}\" is not an account id"))
+}
+
+fn default_vat_percentage() -> Result<i64> {
+    let vat_percentage = std::env::var("CCLI_DEFAULT_VAT_PERCENTAGE").map_err(|_| {
+        anyhow::anyhow!("No VAT percentage given, use --vat-percentage or set CCLI_DEFAULT_VAT_PERCENTAGE")
+    })?;
+
+    vat_percentage.trim().parse::<i64>().map_err(|_| {
+        anyhow::anyhow!("CCLI_DEFAULT_VAT_PERCENTAGE \"{vat_percentage}\" is not a percentage")
+    })
+}
+
+// A quote is valid for the given days, CCLI_QUOTE_VALID_DAYS or else 30 days from now
+fn default_expire_date(valid_days: Option<i64>) -> Result<chrono::NaiveDateTime> {
+    let valid_days = match (valid_days, std::env::var("CCLI_QUOTE_VALID_DAYS")) {
+        (Some(valid_days), _) => valid_days,
+        (None, Ok(valid_days)) => valid_days.trim().parse::<i64>().map_err(|_| {
+            anyhow::anyhow!("CCLI_QUOTE_VALID_DAYS \"{valid_days}\" is not a number of days")
+        })?,
+        (None, Err(_)) => 30,
+    };
+
+    Ok(chrono::Local::now().naive_local() + chrono::Duration::days(valid_days))
+}
+
+// The hours a week that are worked on a project, CCLI_WEEKLY_CAPACITY ie. "32h" or else 40 hours
+fn weekly_capacity() -> Result<Minutes> {
+    match std::env::var("CCLI_WEEKLY_CAPACITY") {
+        Ok(capacity) => capacity
+            .parse::<Minutes>()
+            .map_err(|e| anyhow::anyhow!("CCLI_WEEKLY_CAPACITY: {e}")),
+        Err(_) => Ok(Minutes(40 * 60)),
+    }
+}
+
+// The period on a quote, ie. "01-03-2025 - 28-03-2025". Without an end date the end is estimated
+// from the estimated minutes of the tasks and the weekly capacity, without a start date only the
+// number of weeks is given
+fn project_duration(project: &Project, tasks: &[OrderedTask]) -> Result<String> {
+    let estimated_weeks = || -> Result<i64> {
+        let minutes: i64 = tasks.iter().filter_map(|task| task.task.minutes_estimated).sum();
+        let capacity = weekly_capacity()?.0;
+        Ok((minutes + capacity - 1) / capacity)
+    };
+
+    let end_date = match (project.start_date, project.end_date) {
+        (_, Some(end_date)) => Some(end_date),
+        (Some(start_date), None) => {
+            Some(start_date + chrono::Duration::weeks(estimated_weeks()?.max(1)))
+        }
+        (None, None) => None,
+    };
+
+    Ok(match (project.start_date, end_date) {
+        (Some(start_date), Some(end_date)) => format!(
+            "{} - {}",
+            start_date.format("%d-%m-%Y"),
+            end_date.format("%d-%m-%Y")
+        ),
+        (None, Some(end_date)) => format!("tot {}", end_date.format("%d-%m-%Y")),
+        (_, None) => match estimated_weeks()? {
+            0 => "".to_string(),
+            1 => "1 week".to_string(),
+            weeks => format!("{weeks} weken"),
+        },
+    })
+}
+
+// The quantity and unit columns of a line in the pdf, a fixed price only shows its total
+fn quantity_columns(quantity: f64, unit: &str) -> (String, String) {
+    if unit == "fixed" {
+        return ("".to_string(), "vaste prijs".to_string());
+    }
+
+    let quantity = format!("{quantity:.2}");
+    let quantity = quantity.trim_end_matches('0').trim_end_matches('.').to_string();
+    let unit = match unit {
+        "hour" => "uur",
+        "month" => "maand",
+        unit => unit,
+    };
+
+    (quantity, unit.to_string())
+}
+
+// A subtotal only adds up the quantities when its lines have the same unit
+fn subtotal_quantity_columns(lines: &[(f64, &str)]) -> (String, String) {
+    match lines.first() {
+        Some((_, unit)) if lines.iter().all(|(_, line_unit)| line_unit == unit) => {
+            quantity_columns(lines.iter().map(|(quantity, _)| quantity).sum(), unit)
+        }
+        _ => ("".to_string(), "".to_string()),
+    }
+}
+
+// The table rows of the lines with a subtotal row after each run of lines of the same milestone,
+// lines without a milestone get no subtotal
+fn with_milestone_subtotals<L, R>(
+    lines: &[L],
+    milestone: impl Fn(&L) -> Option<&str>,
+    row: impl Fn(&L) -> R,
+    subtotal: impl Fn(&str, &[&L]) -> R,
+) -> Vec<R> {
+    let mut rows = Vec::new();
+    let mut group: Vec<&L> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        rows.push(row(line));
+        group.push(line);
+
+        let line_milestone = milestone(line);
+        let next_milestone = lines.get(i + 1).and_then(&milestone);
+        if i + 1 == lines.len() || next_milestone != line_milestone {
+            if let Some(line_milestone) = line_milestone {
+                rows.push(subtotal(line_milestone, &group));
+            }
+            group.clear();
+        }
+    }
+
+    rows
+}
+
+// The VAT number of the company of an account, a foreign one means reverse charge
+async fn get_vat_number(db: &SqlitePool, account: &Account) -> Result<Option<String>> {
+    match account.company_id {
+        Some(company_id) => Ok(get_company(db, company_id).await?.vat_number),
+        None => Ok(None),
+    }
+}
+
+fn vat_table(breakdown: &VatBreakdown) -> Vec<self::vat_table::VatTableData> {
+    breakdown
+        .lines
+        .iter()
+        .map(|line| self::vat_table::VatTableData {
+            vat_percentage: format!("{}%", line.vat_percentage),
+            base: line.base.format_amount(),
+            vat: line.vat.format_amount(),
+        })
+        .collect()
+}
+
+fn vat_note(breakdown: &VatBreakdown, recipient_vat_number: Option<&str>) -> String {
+    if breakdown.reverse_charge {
+        format!("{REVERSE_CHARGE_NOTE} {}", recipient_vat_number.unwrap_or(""))
+    } else {
+        "".to_string()
+    }
+}
+
+// The sending account with the company and address printed on quotes and invoices
+async fn get_sender(db: &SqlitePool, sender_id: i64) -> Result<(Account, Company, Address)> {
+    let account = get_account(db, sender_id).await?;
+
+    let company_id = account.company_id.ok_or(anyhow::anyhow!(
+        "Sender {sender_id} has no company to send quotes and invoices from"
+    ))?;
+    let company = get_company(db, company_id).await?;
+
+    let address_id = company.address_id.ok_or(anyhow::anyhow!(
+        "Company {} of sender {sender_id} has no address",
+        company.name
+    ))?;
+    let address = get_address(db, address_id).await?;
+
+    Ok((account, company, address))
+}
+
+/// A quote or invoice line before it is stored, the vat_percentage is the applied rate
+#[derive(Debug, Serialize)]
+pub struct DocumentLine {
+    pub title: String,
+    pub description: Option<String>,
+    pub quantity: f64,
+    pub unit: String,
+    pub unit_price: i64,
+    pub total: i64,
+    pub vat_percentage: i64,
+    pub task_id: Option<i64>,
+    pub contract_id: Option<i64>,
+    pub milestone: Option<String>,
+    pub rate_card: Option<String>,
+}
+
+impl DocumentLine {
+    // The quantity is in minutes for hourly tasks and in units for the others, a fixed price is
+    // one unit. Subtasks are indented below their parent by their depth
+    fn from_task(task: &OrderedTask, quantity: f64, vat_percentage: i64) -> Self {
+        let OrderedTask { task, depth, milestone, rate_card, .. } = task;
+        let (quantity, unit, unit_price, total) = match task.pricing() {
+            TaskPricing::Hourly => {
+                let minute_rate = task.minute_rate.unwrap_or(0);
+                (quantity / 60.0, "hour".to_string(), minute_rate * 60, quantity as i64 * minute_rate)
+            }
+            TaskPricing::Fixed | TaskPricing::PerUnit => {
+                let unit = match task.pricing() {
+                    TaskPricing::Fixed => "fixed".to_string(),
+                    _ => task.unit.clone().unwrap_or("unit".to_string()),
+                };
+                let price = task.price.unwrap_or(0);
+                (quantity, unit, price, (quantity * price as f64).round() as i64)
+            }
+        };
+
+        Self {
+            title: format!("{}{}", "- ".repeat(*depth), task.title),
+            description: task.description.clone(),
+            quantity,
+            unit,
+            unit_price,
+            total,
+            vat_percentage,
+            task_id: Some(task.id),
+            contract_id: None,
+            milestone: milestone.clone(),
+            rate_card: rate_card.clone(),
+        }
+    }
+}
+
+pub async fn get_quote_lines(db: &SqlitePool, quote_id: i64) -> Result<Vec<QuoteLine>> {
+    sqlx::query_as!(
+        QuoteLine,
+        r#"SELECT * FROM quote_lines WHERE quote_id = ? ORDER BY position"#,
+        quote_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+/// The latest revision of the quote of a project, the one that isn't superseded
+pub async fn get_current_quote(db: &SqlitePool, project_id: i64) -> Result<Option<Quote>> {
+    sqlx::query_as!(
+        Quote,
+        r#"SELECT * FROM quotes WHERE project_id = ? AND superseded_by IS NULL ORDER BY revision DESC LIMIT 1"#,
+        project_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+// The revision a new quote of the project gets and the current quote it supersedes, read in the
+// transaction that stores the new quote so two quotes can't take the same revision
+async fn next_quote_revision(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    project_id: Option<i64>,
+) -> Result<(i64, Option<Quote>)> {
+    let Some(project_id) = project_id else {
+        return Ok((1, None));
+    };
+
+    let current_quote = sqlx::query_as!(
+        Quote,
+        r#"SELECT * FROM quotes WHERE project_id = ? AND superseded_by IS NULL ORDER BY revision DESC LIMIT 1"#,
+        project_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if let Some(current_quote) = &current_quote {
+        if current_quote.status() == QuoteStatus::Accepted {
+            return Err(anyhow::anyhow!(
+                "Quote {} of project {} is accepted and can't be superseded",
+                current_quote.id,
+                project_id
+            ));
+        }
+    }
+
+    let revision = sqlx::query_scalar!(
+        r#"SELECT COALESCE(MAX(revision), 0) + 1 AS "revision!: i64" FROM quotes WHERE project_id = ?"#,
+        project_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok((revision, current_quote))
+}
+
+async fn supersede_quote(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    current_quote: Option<&Quote>,
+    quote_id: i64,
+) -> Result<()> {
+    if let Some(current_quote) = current_quote {
+        sqlx::query!(
+            r#"UPDATE quotes SET superseded_by = ? WHERE id = ?"#,
+            quote_id,
+            current_quote.id
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn get_invoice_lines(db: &SqlitePool, invoice_id: i64) -> Result<Vec<InvoiceLine>> {
+    sqlx::query_as!(
+        InvoiceLine,
+        r#"SELECT * FROM invoice_lines WHERE invoice_id = ? ORDER BY position"#,
+        invoice_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+async fn add_quote_lines(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    quote_id: i64,
+    lines: &[DocumentLine],
+) -> Result<()> {
+    for (position, line) in lines.iter().enumerate() {
+        let position = position as i64 + 1;
+        sqlx::query!(
+            r#"
+INSERT INTO quote_lines (
+    quote_id,
+    position,
+    title,
+    description,
+    quantity,
+    unit,
+    unit_price,
+    total,
+    vat_percentage,
+    task_id,
+    milestone,
+    rate_card
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#,
+            quote_id,
+            position,
+            line.title,
+            line.description,
+            line.quantity,
+            line.unit,
+            line.unit_price,
+            line.total,
+            line.vat_percentage,
+            line.task_id,
+            line.milestone,
+            line.rate_card
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn add_invoice_lines(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    invoice_id: i64,
+    lines: &[DocumentLine],
+) -> Result<()> {
+    for (position, line) in lines.iter().enumerate() {
+        let position = position as i64 + 1;
+        sqlx::query!(
+            r#"
+INSERT INTO invoice_lines (
+    invoice_id,
+    position,
+    title,
+    description,
+    quantity,
+    unit,
+    unit_price,
+    total,
+    vat_percentage,
+    task_id,
+    contract_id,
+    milestone,
+    rate_card
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#,
+            invoice_id,
+            position,
+            line.title,
+            line.description,
+            line.quantity,
+            line.unit,
+            line.unit_price,
+            line.total,
+            line.vat_percentage,
+            line.task_id,
+            line.contract_id,
+            line.milestone,
+            line.rate_card
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
 pub async fn make_quote(db: &SqlitePool, quote_args: &QuoteMakeArgs) -> Result<String> {
     let project = sqlx::query_as!(
         Project,
@@ -958,141 +3118,232 @@ pub async fn make_quote(db: &SqlitePool, quote_args: &QuoteMakeArgs) -> Result<S
     .fetch_one(db)
     .await?;
 
-    let project_tasks = sqlx::query_as!(
-        ProjectTask,
-        r#"SELECT * FROM tasks WHERE project_id = ?"#,
-        quote_args.project_id
-    )
-    .fetch_all(db)
-    .await?;
+    let mut project_tasks = get_ordered_project_tasks(db, quote_args.project_id).await?;
 
-    let vat_percentage = quote_args.vat_percentage.unwrap_or(21);
+    let sender_id = match quote_args.sender_id {
+        Some(sender_id) => sender_id,
+        None => default_sender_id()?,
+    };
+    let (_, sender, _) = get_sender(db, sender_id).await?;
+    let recipient = get_account(db, project.client_id).await?;
+    let recipient_vat_number = get_vat_number(db, &recipient).await?;
+    let reverse_charge =
+        is_reverse_charge(sender.vat_number.as_deref(), recipient_vat_number.as_deref());
+    apply_rate_cards(db, &mut project_tasks, sender.id, project.client_id).await?;
+
+    let vat_percentage = match quote_args.vat_percentage {
+        Some(vat_percentage) => vat_percentage,
+        None => default_vat_percentage()?,
+    };
     let currency = quote_args.currency.clone().unwrap_or("EUR".to_string());
-    let total_before_vat = project_tasks.iter().fold(0, |acc, task| {
-        acc + task.minutes_estimated.unwrap_or(0) * task.minute_rate.unwrap_or(0)
-    });
-    let total_after_vat =
-        (total_before_vat - quote_args.discount.unwrap_or(0)) * (100 + vat_percentage);
-    let project_duration = format!(
-        "{} - {}",
-        project
-            .start_date
-            .expect("No project.start_date")
-            .format("%d-%m-%Y"),
-        project
-            .end_date
-            .expect("No project.end_date")
-            .format("%d-%m-%Y")
+    let lines = project_tasks
+        .iter()
+        .map(|ordered_task| {
+            let task = &ordered_task.task;
+            let vat_percentage = if reverse_charge {
+                0
+            } else {
+                task.vat_percentage.unwrap_or(vat_percentage)
+            };
+            let quantity = match task.pricing() {
+                TaskPricing::Hourly => task.minutes_estimated.unwrap_or(0) as f64,
+                TaskPricing::Fixed => 1.0,
+                TaskPricing::PerUnit => task.quantity.unwrap_or(0.0),
+            };
+            DocumentLine::from_task(ordered_task, quantity, vat_percentage)
+        })
+        .collect::<Vec<_>>();
+    let line_totals = lines
+        .iter()
+        .map(|line| (line.total, line.vat_percentage))
+        .collect::<Vec<_>>();
+    let total_before_vat = lines.iter().map(|line| line.total).sum();
+    let vat_breakdown = VatBreakdown::new(
+        &line_totals,
+        quote_args.discount.unwrap_or(0),
+        &currency,
+        reverse_charge,
     );
+    let project_duration = project_duration(&project, &project_tasks)?;
 
     let quote = QuoteCreateArgs {
-        sender_id: 1,
+        sender_id,
         recipient_id: project.client_id,
         project_id: Some(quote_args.project_id),
         project_duration: Some(project_duration),
         remarks: quote_args.remarks.clone(),
         total_before_vat: Some(total_before_vat),
         discount: quote_args.discount,
-        vat_percentage: Some(vat_percentage),
+        vat_percentage: vat_breakdown.vat_percentage(),
         currency: Some(currency.clone()),
-        total_after_vat: Some(total_after_vat),
+        total_after_vat: Some(vat_breakdown.total_after_vat().amount),
         quote_url: None,
+        expire_date: Some(default_expire_date(quote_args.valid_days)?),
+        reverse_charge,
     };
 
-    let sender_account = sqlx::query_as!(
-        Account,
-        r#"SELECT * FROM accounts WHERE id = ?"#,
-        quote.sender_id
+    // The quote and its lines are committed first and the pdf is rendered from them, a failed
+    // pdf can be rendered again with render-quote. The new quote is the next revision of the
+    // project and supersedes the current one
+    let mut tx = db.begin().await?;
+    let (revision, current_quote) = next_quote_revision(&mut tx, quote.project_id).await?;
+    let result = sqlx::query!(
+        r#"
+INSERT INTO quotes (
+    sender_id,
+    recipient_id,
+    project_id,
+    project_duration,
+    remarks,
+    total_before_vat,
+    discount,
+    vat_percentage,
+    currency,
+    total_after_vat,
+    expire_date,
+    reverse_charge,
+    revision
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#,
+        quote.sender_id,
+        quote.recipient_id,
+        quote.project_id,
+        quote.project_duration,
+        quote.remarks,
+        quote.total_before_vat,
+        quote.discount,
+        quote.vat_percentage,
+        currency,
+        quote.total_after_vat,
+        quote.expire_date,
+        quote.reverse_charge,
+        revision
     )
-    .fetch_one(db)
+    .execute(&mut *tx)
     .await?;
 
-    let sender = sqlx::query_as!(
-        Company,
-        r#"SELECT * FROM companies WHERE id = ?"#,
-        sender_account.company_id
-    )
-    .fetch_one(db)
-    .await?;
+    if result.rows_affected() == 0 {
+        return Err(anyhow::anyhow!("Failed to insert quote"));
+    }
 
-    let sender_address = sqlx::query_as!(
-        Address,
-        r#"SELECT * FROM address WHERE id = ?"#,
-        sender.address_id
-    )
-    .fetch_one(db)
-    .await?;
+    let quote_id = result.last_insert_rowid();
+    add_quote_lines(&mut tx, quote_id, &lines).await?;
+    supersede_quote(&mut tx, current_quote.as_ref(), quote_id).await?;
+
+    tx.commit().await?;
+
+    render_quote(db, quote_id).await
+}
+
+/// Render the pdf of a stored quote again from its lines and update the quote_url
+pub async fn render_quote(db: &SqlitePool, id: i64) -> Result<String> {
+    let quote = get_quote(db, id).await?;
+    let lines = get_quote_lines(db, id).await?;
+    let quote_url = render_quote_pdf(db, &quote, &lines).await?;
+
+    sqlx::query!(r#"UPDATE quotes SET quote_url = ? WHERE id = ?"#, quote_url, id)
+        .execute(db)
+        .await?;
+
+    Ok(quote_url)
+}
+
+/// Compare a quote with another revision, by default the revision it superseded
+pub async fn diff_quote(db: &SqlitePool, id: i64, against: Option<i64>) -> Result<QuoteDiff> {
+    let quote = get_quote(db, id).await?;
+    let old_quote = match against {
+        Some(against) => get_quote(db, against).await?,
+        None => sqlx::query_as!(Quote, r#"SELECT * FROM quotes WHERE superseded_by = ?"#, id)
+            .fetch_optional(db)
+            .await?
+            .ok_or(anyhow::anyhow!(
+                "Quote {id} doesn't supersede another revision, use --against"
+            ))?,
+    };
+
+    let lines = get_quote_lines(db, quote.id).await?;
+    let old_lines = get_quote_lines(db, old_quote.id).await?;
+
+    Ok(QuoteDiff::new(&old_quote, &old_lines, &quote, &lines))
+}
+
+// The name of a status as it is given on the command line, ie. "in-progress"
+fn value_name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map_or("".to_string(), |value| value.get_name().to_string())
+}
+
+// Only a quote that is still open can be answered
+fn check_quote_open(quote: &Quote) -> Result<()> {
+    match quote.status() {
+        QuoteStatus::Draft | QuoteStatus::Sent => Ok(()),
+        status => Err(anyhow::anyhow!("Quote {} is {}", quote.id, value_name(&status))),
+    }
+}
 
-    let recipient = sqlx::query_as!(
-        Account,
-        r#"SELECT * FROM accounts WHERE id = ?"#,
-        project.client_id
+/// Accept a quote and open its project, `accepted_by` defaults to the name of the recipient
+pub async fn accept_quote(db: &SqlitePool, id: i64, accepted_by: Option<&str>) -> Result<Quote> {
+    let quote = get_quote(db, id).await?;
+    check_quote_open(&quote)?;
+
+    let accepted_by = match accepted_by {
+        Some(accepted_by) => accepted_by.to_string(),
+        None => get_account(db, quote.recipient_id)
+            .await?
+            .name
+            .unwrap_or("".to_string()),
+    };
+
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        r#"UPDATE quotes SET
+        accepted_date = CURRENT_TIMESTAMP,
+        accepted_by = ?
+        WHERE id = ?"#,
+        accepted_by,
+        id
     )
-    .fetch_one(db)
+    .execute(&mut *tx)
     .await?;
 
-    let mut recipient_address: Option<Address> = None;
-    let mut recipient_company_name: Option<String> = None;
-
-    if recipient.company_id.is_some() {
-        let recipient_company = sqlx::query_as!(
-            Company,
-            r#"SELECT * FROM companies WHERE id = ?"#,
-            recipient.company_id
-        )
-        .fetch_one(db)
-        .await?;
-
-        recipient_company_name = Some(recipient_company.name.clone());
-
-        let result = sqlx::query_as!(
-            Address,
-            r#"SELECT * FROM address WHERE id = ?"#,
-            recipient_company.address_id
-        )
-        .fetch_one(db)
-        .await;
-        recipient_address = match result {
-            Ok(address) => Some(address),
-            Err(_) => None,
-        };
+    // A project that was already started keeps its status
+    if let Some(project_id) = quote.project_id {
+        let project = get_project(db, project_id).await?;
+        if project.status() == ProjectStatus::PendingApproval {
+            let note = format!("Quote {id} accepted");
+            change_project_status(
+                &mut tx,
+                project_id,
+                ProjectStatus::PendingApproval,
+                ProjectStatus::Open,
+                Some(&note),
+            )
+            .await?;
+        }
     }
+    tx.commit().await?;
 
-    if recipient.address_id.is_some() && recipient_address.is_none() {
-        let result = sqlx::query_as!(
-            Address,
-            r#"SELECT * FROM address WHERE id = ?"#,
-            recipient.address_id
-        )
-        .fetch_one(db)
-        .await;
-        recipient_address = match result {
-            Ok(address) => Some(address),
-            Err(_) => None,
-        };
-    }
+    get_quote(db, id).await
+}
 
-    let mut quote_table = Vec::new();
+pub async fn reject_quote(db: &SqlitePool, id: i64) -> Result<Quote> {
+    let quote = get_quote(db, id).await?;
+    check_quote_open(&quote)?;
 
-    for project_task in project_tasks {
-        quote_table.push(self::quote::QuoteTableData {
-            title: project_task.title.clone(),
-            description: project_task.description.clone().unwrap_or("".to_string()),
-            hours_estimated: project_task.minutes_estimated.unwrap_or(0) as f64 / 60.0,
-            hourly_rate: format!(
-                "{:.2}",
-                (project_task.minute_rate.unwrap_or(0) * 60) as f64 / 100.0
-            ),
-            total: format!(
-                "{:.2}",
-                (project_task.minutes_estimated.unwrap_or(0)
-                    * project_task.minute_rate.unwrap_or(0)) as f64
-                    / 100.0
-            ),
-        });
-    }
+    sqlx::query!(
+        r#"UPDATE quotes SET rejected_date = CURRENT_TIMESTAMP WHERE id = ?"#,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    get_quote(db, id).await
+}
 
-    let (recipient_street, recipient_postal_city) = match recipient_address {
+// The street and postal code with city lines of an address on documents
+fn address_lines(address: Option<&Address>) -> (String, String) {
+    match address {
         Some(address) => (
             format!(
                 "{} {} {}",
@@ -1107,48 +3358,113 @@ pub async fn make_quote(db: &SqlitePool, quote_args: &QuoteMakeArgs) -> Result<S
             ),
         ),
         None => ("".to_string(), "".to_string()),
+    }
+}
+
+// The quote pdf is built from the stored quote and lines only, so rendering it again gives the
+// same document. The file is named after the project and send date and is overwritten
+async fn render_quote_pdf(db: &SqlitePool, quote: &Quote, lines: &[QuoteLine]) -> Result<String> {
+    if lines.is_empty() && quote.total_before_vat != 0 {
+        return Err(anyhow::anyhow!(
+            "Quote {} was made before its lines were stored and can't be rendered, make a new quote",
+            quote.id
+        ));
+    }
+
+    let (_, sender, sender_address) = get_sender(db, quote.sender_id).await?;
+    let (recipient, recipient_company_name, recipient_address) =
+        get_recipient(db, quote.recipient_id).await?;
+    let recipient_vat_number = get_vat_number(db, &recipient).await?;
+    let project = match quote.project_id {
+        Some(project_id) => Some(get_project(db, project_id).await?),
+        None => None,
     };
 
-    let vat_amount = quote.total_after_vat.unwrap_or(0)
-        - ((quote.total_before_vat.unwrap_or(0) - quote.discount.unwrap_or(0)) * 100);
+    let line_totals = lines
+        .iter()
+        .map(|line| (line.total, line.vat_percentage))
+        .collect::<Vec<_>>();
+    let vat_breakdown = VatBreakdown::new(
+        &line_totals,
+        quote.discount.unwrap_or(0),
+        &quote.currency,
+        quote.reverse_charge,
+    );
+    let total_after_vat = vat_breakdown.total_after_vat();
+
+    let quote_table = with_milestone_subtotals(
+        lines,
+        |line| line.milestone.as_deref(),
+        |line| {
+            let (quantity, unit) = quantity_columns(line.quantity, &line.unit);
+            self::quote::QuoteTableData {
+                title: line.title.clone(),
+                description: line.description.clone().unwrap_or("".to_string()),
+                quantity,
+                unit,
+                unit_price: match line.unit.as_str() {
+                    "fixed" => "".to_string(),
+                    _ => quote.money(line.unit_price).format_amount(),
+                },
+                rate_card: line.rate_card.clone().unwrap_or("".to_string()),
+                total: quote.money(line.total).format_amount(),
+            }
+        },
+        |milestone, group| {
+            let (quantity, unit) = subtotal_quantity_columns(
+                &group.iter().map(|line| (line.quantity, line.unit.as_str())).collect::<Vec<_>>(),
+            );
+            self::quote::QuoteTableData {
+                title: format!("Subtotaal {milestone}"),
+                description: "".to_string(),
+                quantity,
+                unit,
+                unit_price: "".to_string(),
+                rate_card: "".to_string(),
+                total: quote.money(group.iter().map(|line| line.total).sum()).format_amount(),
+            }
+        },
+    );
+
+    let (sender_street, sender_postal_city) = address_lines(Some(&sender_address));
+    let (recipient_street, recipient_postal_city) = address_lines(recipient_address.as_ref());
 
     let quote_template = self::quote::QuoteTemplate {
         sender_name: sender.name.clone(),
-        sender_street: format!(
-            "{} {} {}",
-            sender_address.street.clone().unwrap_or("".to_string()),
-            sender_address.number.clone().unwrap_or("".to_string()),
-            sender_address.unit.clone().unwrap_or("".to_string()),
-        ),
-        sender_postal_city: format!(
-            "{} {}",
-            sender_address.postalcode.clone().unwrap_or("".to_string()),
-            sender_address.city.clone().unwrap_or("".to_string()),
-        ),
+        sender_street,
+        sender_postal_city,
         sender_phone: sender.phone.clone().unwrap_or("".to_string()),
         sender_email: sender.email.clone().unwrap_or("".to_string()),
+        sender_company_name: sender.name.clone(),
         sender_commerce_number: sender.commerce_number.clone().unwrap_or("".to_string()),
+        sender_vat_number: sender.vat_number.clone().unwrap_or("".to_string()),
+        sender_iban: sender.iban.clone().unwrap_or("".to_string()),
         recipient_name: recipient.name.clone().unwrap_or("".to_string()),
-        recipient_company_name: recipient_company_name.clone().unwrap_or("".to_string()),
+        recipient_company_name: recipient_company_name.unwrap_or("".to_string()),
         recipient_street,
         recipient_postal_city,
-        send_date: chrono::Local::now().format("%d-%m-%Y").to_string(),
-        project_title: project.title.clone(),
-        project_description: project.description.clone().unwrap_or("".to_string()),
+        send_date: quote
+            .send_date
+            .unwrap_or(quote.created_at)
+            .format("%d-%m-%Y")
+            .to_string(),
+        quote_id: quote.id.to_string(),
+        revision: format!("v{}", quote.revision),
+        project_title: project.as_ref().map_or("".to_string(), |project| project.title.clone()),
+        project_description: project
+            .as_ref()
+            .and_then(|project| project.description.clone())
+            .unwrap_or("".to_string()),
         project_duration: quote.project_duration.clone().unwrap_or("".to_string()),
         project_tasks: quote_table,
         remarks: quote.remarks.clone().unwrap_or("".to_string()),
-        currency_symbol: match quote.currency.clone().unwrap_or("EUR".to_string()).as_str() {
-            "EUR" => "€".to_string(),
-            "USD" => "$".to_string(),
-            _ => "".to_string(),
-        }
-        .to_string(),
-        total_before_vat: format!("{:.2}", quote.total_before_vat.unwrap_or(0) as f64 / 100.0),
-        discount: format!("{:.2}", quote.discount.unwrap_or(0) as f64 / 100.0),
-        vat_percentage: quote.vat_percentage.unwrap_or(21).to_string(),
-        vat_amount: format!("{:.2}", vat_amount as f64 / 10000.0),
-        total_after_vat: format!("{:.2}", quote.total_after_vat.unwrap_or(0) as f64 / 10000.0),
+        currency_symbol: total_after_vat.symbol(),
+        total_before_vat: quote.money(quote.total_before_vat).format_amount(),
+        discount: quote.money(quote.discount.unwrap_or(0)).format_amount(),
+        vat_lines: vat_table(&vat_breakdown),
+        vat_note: vat_note(&vat_breakdown, recipient_vat_number.as_deref()),
+        vat_amount: vat_breakdown.vat().format_amount(),
+        total_after_vat: total_after_vat.format_amount(),
     };
 
     let pdf_args = PdfArgs {
@@ -1156,44 +3472,241 @@ pub async fn make_quote(db: &SqlitePool, quote_args: &QuoteMakeArgs) -> Result<S
         data: PdfData::Quote(&quote_template),
     };
 
-    let quote_url = generate_pdf(&pdf_args).await?;
+    generate_pdf(&pdf_args).await
+}
 
-    let result = sqlx::query!(
+pub async fn get_invoice_sequence(
+    db: &SqlitePool,
+    company_id: i64,
+    document: SequenceDocument,
+) -> Result<InvoiceSequence> {
+    let document = document.as_str();
+    sqlx::query_as!(
+        InvoiceSequence,
+        r#"SELECT * FROM invoice_sequences WHERE company_id = ? AND document = ?"#,
+        company_id,
+        document
+    )
+    .fetch_one(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+pub async fn set_invoice_sequence(
+    db: &SqlitePool,
+    company_id: i64,
+    document: SequenceDocument,
+    sequence: &InvoiceSequenceArgs,
+) -> Result<InvoiceSequence> {
+    if let Some(format) = &sequence.format {
+        format_invoice_number(format, 1, chrono::Local::now().naive_local())?;
+    }
+
+    let document_name = document.as_str();
+    let default_format = document.default_format();
+    sqlx::query!(
+        r#"INSERT OR IGNORE INTO invoice_sequences (company_id, document, format) VALUES (?, ?, ?)"#,
+        company_id,
+        document_name,
+        default_format
+    )
+    .execute(db)
+    .await?;
+
+    // Setting the next number starts the current year, so a yearly reset doesn't undo it
+    let year = chrono::Local::now().year();
+    sqlx::query!(
+        r#"UPDATE invoice_sequences SET
+        format = COALESCE(?, format),
+        yearly_reset = COALESCE(?, yearly_reset),
+        next_number = COALESCE(?, next_number),
+        year = CASE WHEN ? IS NULL THEN year ELSE ? END
+        WHERE company_id = ? AND document = ?"#,
+        sequence.format,
+        sequence.yearly_reset,
+        sequence.next_number,
+        sequence.next_number,
+        year,
+        company_id,
+        document_name
+    )
+    .execute(db)
+    .await?;
+
+    get_invoice_sequence(db, company_id, document).await
+}
+
+/// Format an invoice number from a pattern with the tokens
+/// {YYYY}, {YY}, {MM}, {seq} and {seq:0N} for a zero padded sequence of N digits
+pub fn format_invoice_number(
+    format: &str,
+    seq: i64,
+    date: chrono::NaiveDateTime,
+) -> Result<String> {
+    if !format.contains("{seq") {
+        return Err(anyhow::anyhow!(
+            "Invoice number format \"{format}\" needs a {{seq}} token"
+        ));
+    }
+
+    let mut number = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        number.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or(anyhow::anyhow!("Unclosed token in invoice number format \"{format}\""))?
+            + start;
+        let token = &rest[start + 1..end];
+
+        match token {
+            "YYYY" => number.push_str(&format!("{:04}", date.year())),
+            "YY" => number.push_str(&format!("{:02}", date.year() % 100)),
+            "MM" => number.push_str(&format!("{:02}", date.month())),
+            "seq" => number.push_str(&seq.to_string()),
+            _ => match token.strip_prefix("seq:") {
+                Some(width) => {
+                    let width = width.parse::<usize>().map_err(|_| {
+                        anyhow::anyhow!("Invalid sequence width in \"{{{token}}}\"")
+                    })?;
+                    number.push_str(&format!("{seq:0width$}"));
+                }
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "Unknown token {{{token}}} in invoice number format \"{format}\""
+                    ))
+                }
+            },
+        }
+
+        rest = &rest[end + 1..];
+    }
+    number.push_str(rest);
+
+    Ok(number)
+}
+
+// Numbers are unique per sending company, or per sender account when it has no company
+async fn invoice_number_exists<'e, E: sqlx::SqliteExecutor<'e>>(
+    executor: E,
+    sender_id: i64,
+    invoice_number: &str,
+    exclude_id: Option<i64>,
+) -> Result<bool> {
+    let count = sqlx::query_scalar!(
         r#"
-INSERT INTO quotes (
-    sender_id,
-    recipient_id,
-    project_id,
-    project_duration,
-    remarks,
-    total_before_vat,
-    discount,
-    vat_percentage,
-    currency,
-    total_after_vat,
-    quote_url
-) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+SELECT COUNT(*) AS "count: i64" FROM invoices
+WHERE invoice_number = $1
+AND (sender_id = $2 OR sender_id IN (
+    SELECT id FROM accounts
+    WHERE company_id = (SELECT company_id FROM accounts WHERE id = $2)
+))
+AND ($3 IS NULL OR id != $3)
 "#,
-        quote.sender_id,
-        quote.recipient_id,
-        quote.project_id,
-        quote.project_duration,
-        quote.remarks,
-        quote.total_before_vat,
-        quote.discount,
-        vat_percentage,
-        currency,
-        quote.total_after_vat,
-        quote_url
+        invoice_number,
+        sender_id,
+        exclude_id
     )
-    .execute(db)
+    .fetch_one(executor)
     .await?;
 
-    if result.rows_affected() == 0 {
-        return Err(anyhow::anyhow!("Failed to insert quote"));
+    Ok(count > 0)
+}
+
+// Credit note numbers follow the same rule as invoice numbers
+async fn credit_note_number_exists<'e, E: sqlx::SqliteExecutor<'e>>(
+    executor: E,
+    sender_id: i64,
+    credit_note_number: &str,
+) -> Result<bool> {
+    let count = sqlx::query_scalar!(
+        r#"
+SELECT COUNT(*) AS "count: i64" FROM credit_notes
+WHERE credit_note_number = $1
+AND (sender_id = $2 OR sender_id IN (
+    SELECT id FROM accounts
+    WHERE company_id = (SELECT company_id FROM accounts WHERE id = $2)
+))
+"#,
+        credit_note_number,
+        sender_id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(count > 0)
+}
+
+async fn allocate_document_number(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    sender_id: i64,
+    document: SequenceDocument,
+) -> Result<String> {
+    let company_id = sqlx::query_scalar!(
+        r#"SELECT company_id FROM accounts WHERE id = ?"#,
+        sender_id
+    )
+    .fetch_one(&mut **tx)
+    .await?
+    .ok_or(anyhow::anyhow!("Sender {sender_id} has no company to number documents for"))?;
+
+    let document_name = document.as_str();
+    let default_format = document.default_format();
+    sqlx::query!(
+        r#"INSERT OR IGNORE INTO invoice_sequences (company_id, document, format) VALUES (?, ?, ?)"#,
+        company_id,
+        document_name,
+        default_format
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    let sequence = sqlx::query_as!(
+        InvoiceSequence,
+        r#"SELECT * FROM invoice_sequences WHERE company_id = ? AND document = ?"#,
+        company_id,
+        document_name
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    let now = chrono::Local::now().naive_local();
+    let year = i64::from(now.year());
+    let mut seq = if sequence.yearly_reset && sequence.year != Some(year) {
+        1
+    } else {
+        sequence.next_number
+    };
+    let mut number = format_invoice_number(&sequence.format, seq, now)?;
+
+    // Skip numbers that are already taken, ie. typed in by hand or made before the sequence existed
+    loop {
+        let exists = match document {
+            SequenceDocument::Invoice => {
+                invoice_number_exists(&mut **tx, sender_id, &number, None).await?
+            }
+            SequenceDocument::CreditNote => {
+                credit_note_number_exists(&mut **tx, sender_id, &number).await?
+            }
+        };
+        if !exists {
+            break;
+        }
+        seq += 1;
+        number = format_invoice_number(&sequence.format, seq, now)?;
     }
 
-    Ok(quote_url)
+    let next_number = seq + 1;
+    sqlx::query!(
+        r#"UPDATE invoice_sequences SET next_number = ?, year = ? WHERE id = ?"#,
+        next_number,
+        year,
+        sequence.id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(number)
 }
 
 pub async fn get_invoice(db: &SqlitePool, id: i64) -> Result<Invoice> {
@@ -1204,6 +3717,13 @@ pub async fn get_invoice(db: &SqlitePool, id: i64) -> Result<Invoice> {
 }
 
 pub async fn add_invoice(db: &SqlitePool, invoice: &InvoiceCreateArgs) -> Result<i64> {
+    if invoice_number_exists(db, invoice.sender_id, &invoice.invoice_number, None).await? {
+        return Err(anyhow::anyhow!(
+            "Invoice number {} is already used by this sender",
+            invoice.invoice_number
+        ));
+    }
+
     let result = sqlx::query!(
         r#"
 INSERT INTO invoices (
@@ -1222,8 +3742,11 @@ INSERT INTO invoices (
     currency,
     total_after_vat,
     invoice_url,
-    payment_request_url
-) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    payment_request_url,
+    reverse_charge,
+    period_start,
+    period_end
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 "#,
         invoice.sender_id,
         invoice.recipient_id,
@@ -1240,7 +3763,10 @@ INSERT INTO invoices (
         invoice.currency,
         invoice.total_after_vat,
         invoice.invoice_url,
-        invoice.payment_request_url
+        invoice.payment_request_url,
+        invoice.reverse_charge,
+        invoice.period_start,
+        invoice.period_end
     )
     .execute(db)
     .await?;
@@ -1252,12 +3778,105 @@ INSERT INTO invoices (
     Ok(result.last_insert_rowid())
 }
 
-pub async fn make_invoice(db: &SqlitePool, invoice_args: &InvoiceMakeArgs) -> Result<String> {
-    let mut sender_id = 1;
-    let mut vat_percentage = 21;
+// The consecutive periods of whole calendar months from `start` that have begun by `until`, up to
+// the end of the contract, with the months they bill. Every period is counted from `start`, so
+// month ends don't drift. The period the contract ends in is cut off at the end and bills the
+// part of its months in proportion to its days
+fn contract_periods(
+    start: chrono::NaiveDateTime,
+    period_months: u32,
+    until: chrono::NaiveDateTime,
+    contract_end: Option<chrono::NaiveDateTime>,
+) -> Result<Vec<(chrono::NaiveDateTime, chrono::NaiveDateTime, f64)>> {
+    let mut periods = Vec::new();
+    let mut period_start = start;
+
+    while period_start <= until && contract_end.is_none_or(|end| period_start < end) {
+        let months = (periods.len() as u32 + 1) * period_months;
+        let full_end = Interval::months(months).add_to(start)?;
+        let period_end = contract_end.map_or(full_end, |end| end.min(full_end));
+        let billed_months = (period_months as f64 * 100.0
+            * (period_end - period_start).num_seconds() as f64
+            / (full_end - period_start).num_seconds() as f64)
+            .round()
+            / 100.0;
+        periods.push((period_start, period_end, billed_months));
+        period_start = period_end;
+    }
+
+    Ok(periods)
+}
+
+// ie. "March 2025", "January - March 2025" or "December 2024 - February 2025", periods that
+// aren't whole calendar months are described by their days
+fn period_description(start: chrono::NaiveDateTime, end: chrono::NaiveDateTime) -> String {
+    let last_day = end - chrono::Duration::days(1);
+
+    if start.day() != 1 || end.day() != 1 {
+        format!("{} - {}", start.format("%d-%m-%Y"), last_day.format("%d-%m-%Y"))
+    } else if start.year() != last_day.year() {
+        format!("{} - {}", start.format("%B %Y"), last_day.format("%B %Y"))
+    } else if start.month() != last_day.month() {
+        format!("{} - {}", start.format("%B"), last_day.format("%B %Y"))
+    } else {
+        start.format("%B %Y").to_string()
+    }
+}
+
+// An invoice and its lines before they are stored, with the minutes billed per task, the time
+// entries the invoice covers and the part of its discount that comes from its quote
+struct InvoiceDraft {
+    invoice: InvoiceCreateArgs,
+    quote_discount: i64,
+    lines: Vec<DocumentLine>,
+    billed_minutes: Vec<(i64, i64, i64)>,
+    billed_quantities: Vec<(i64, f64, f64)>,
+    time_entries: Vec<TimeEntry>,
+}
+
+// The discount of a quote is spread over the invoices made from it in proportion to what they bill,
+// so every instalment doesn't get the whole discount again. Once the quote is billed in full all
+// of its discount is applied
+async fn quote_discount_share(db: &SqlitePool, quote: &Quote, billed: i64) -> Result<i64> {
+    let discount = quote.discount.unwrap_or(0);
+    if discount <= 0 {
+        return Ok(0);
+    }
+
+    let invoiced = sqlx::query!(
+        r#"SELECT
+        COALESCE(SUM(total_before_vat), 0) AS "total_before_vat!: i64",
+        COALESCE(SUM(quote_discount), 0) AS "quote_discount!: i64"
+        FROM invoices WHERE quote_id = ?"#,
+        quote.id
+    )
+    .fetch_one(db)
+    .await?;
+
+    let due = if quote.total_before_vat <= 0 {
+        discount
+    } else {
+        (discount as i128 * (invoiced.total_before_vat + billed) as i128
+            / quote.total_before_vat as i128)
+            .min(discount as i128) as i64
+    };
+
+    Ok((due - invoiced.quote_discount).max(0))
+}
+
+// Nothing is written yet, the invoice number is left empty and allocated when the draft is
+// stored, so a dry run doesn't leave a gap in the sequence
+async fn prepare_invoice(db: &SqlitePool, invoice_args: &InvoiceMakeArgs) -> Result<InvoiceDraft> {
+    let mut sender_id = invoice_args.sender_id;
+    let mut vat_percentage = invoice_args.vat_percentage;
     let mut discount = invoice_args.discount.unwrap_or(0);
     let mut currency = "EUR".to_string();
     let invoice: InvoiceCreateArgs;
+    let mut lines = Vec::new();
+    let mut billed_minutes = Vec::new();
+    let mut billed_quantities = Vec::new();
+    let mut time_entries: Vec<TimeEntry> = Vec::new();
+    let mut quote_discount = 0;
 
     match (invoice_args.quote_id, invoice_args.project_id, invoice_args.contract_id) {
         (Some(_), None, None)
@@ -1270,284 +3889,143 @@ pub async fn make_invoice(db: &SqlitePool, invoice_args: &InvoiceMakeArgs) -> Re
         } 
     }
 
+    if invoice_args.milestone_id.is_some() && invoice_args.contract_id.is_some() {
+        return Err(anyhow::anyhow!(
+            "A milestone is invoiced from its project or quote, not from a contract"
+        ));
+    }
 
-    let invoice_url = if invoice_args.contract_id.is_some() {
-        let mut invoice_table = Vec::new();
-        let contract_id = invoice_args.contract_id.unwrap();
-
+    if let Some(contract_id) = invoice_args.contract_id {
         let contract = sqlx::query_as!(
             Contract,
             r#"SELECT * FROM contracts WHERE id = ?"#,
-            contract_id
-        )
-        .fetch_one(db)
-        .await?;
-
-        #[derive(sqlx::FromRow, Debug)]
-        struct InvoiceRecord {
-            invoice_number: String,
-        }
-
-        let last_invoice = sqlx::query_as!(InvoiceRecord,
-            r#"SELECT invoice_number FROM invoices ORDER BY id DESC LIMIT 1"#,
-        ).fetch_one(db).await.unwrap_or(InvoiceRecord { invoice_number: format!("{}{:05}", chrono::Utc::now().year(), 0) });
-        
-
-        let last_invoice_year = last_invoice.invoice_number.chars().take(4).collect::<String>();
-        let new_invoice_number = if last_invoice_year == chrono::Utc::now().year().to_string() {
-             (last_invoice.invoice_number.parse::<usize>().unwrap_or(0) + 1).to_string()
-        } else {
-            format!("{}{:05}", chrono::Utc::now().year(), 1)
-        };
-        
-
-
-        let last_contract_invoice = sqlx::query_as!(
-            Invoice,
-            r#"SELECT * FROM invoices WHERE contract_id = ? ORDER BY id DESC LIMIT 1"#,
-            invoice_args.contract_id
-        )
-        .fetch_one(db)
-        .await;
-
-        let (last_invoiced_date, duration) = match last_contract_invoice {
-            Ok(inv) => {
-                let last_invoiced_date = inv.payment_date.unwrap_or(
-                    inv
-                        .send_date
-                        .unwrap_or(contract.start_date.unwrap()),
-                );
-                (last_invoiced_date, chrono::Utc::now().signed_duration_since(last_invoiced_date.and_utc()))
-            }
-            Err(_) => {
-                let last_invoiced_date = contract.start_date.unwrap();
-                (last_invoiced_date, chrono::Utc::now().signed_duration_since(last_invoiced_date.and_utc()))
-            }
-        };
-        let total_months = duration.num_weeks() * 52 / 12;
-        let mut total_before_vat = 0;
-
-        for i in (0..=total_months).step_by(contract.invoice_period_months.unwrap_or(1) as usize) {
-            let total =
-                contract.monthly_rate.unwrap_or(0) * contract.invoice_period_months.unwrap_or(1);
-            let first_month = last_invoiced_date
-                .checked_add_signed(chrono::Duration::weeks(i))
-                .unwrap();
-            let last_month = first_month
-                .checked_add_signed(chrono::Duration::weeks(
-                    contract.invoice_period_months.unwrap_or(1),
-                ))
-                .unwrap();
-            let is_new_year = first_month.year() != last_month.year();
-            let description = if is_new_year {
-                format!(
-                    "{} {} - {} {}",
-                    first_month.format("%B"),
-                    first_month.year(),
-                    last_month.format("%B"),
-                    last_month.year()
-                )
-            } else {
-                format!("{} - {}", first_month.format("%B"), last_month.format("%B"))
-            };
-
-            total_before_vat += total;
-
-            invoice_table.push(self::invoice_maintenance::InvoiceMaintenanceTableData {
-                description,
-                months: contract.invoice_period_months.unwrap_or(1),
-                monthly_rate: format!("{:.2}", contract.monthly_rate.unwrap_or(0) as f64 / 100.0),
-                total: format!("{:.2}", total as f64 / 100.0),
-            });
-        }
-
-        
-
-        let total_after_vat = (total_before_vat - discount) * (100 + vat_percentage);
-    
-        invoice = InvoiceCreateArgs {
-            sender_id: contract.sender_id,
-            recipient_id: contract.recipient_id,
-            invoice_number: new_invoice_number,
-            send_date: Some(chrono::Utc::now().naive_local()),
-            quote_id: None,
-            payment_due_date: Some(
-                chrono::Utc::now()
-                    .checked_add_months(Months::new(1))
-                    .unwrap()
-                    .naive_local()),
-            payment_date: None,
-            contract_id: invoice_args.contract_id,
-            project_id: None,
-            remarks: invoice_args.remarks.clone(),
-            total_before_vat: Some(total_before_vat),
-            discount: Some(discount),
-            currency: Some(currency),
-            vat_percentage: Some(vat_percentage),
-            total_after_vat: Some(total_after_vat),
-            invoice_url: None,
-            payment_request_url: None,
-        };
-
-        let sender_account = sqlx::query_as!(
-            Account,
-            r#"SELECT * FROM accounts WHERE id = ?"#,
-            invoice.sender_id
-        )
-        .fetch_one(db)
-        .await?;
-    
-        let sender = sqlx::query_as!(
-            Company,
-            r#"SELECT * FROM companies WHERE id = ?"#,
-            sender_account.company_id
-        )
-        .fetch_one(db)
-        .await?;
-    
-        let sender_address = sqlx::query_as!(
-            Address,
-            r#"SELECT * FROM address WHERE id = ?"#,
-            sender.address_id
-        )
-        .fetch_one(db)
-        .await?;
-    
-        let recipient = sqlx::query_as!(
-            Account,
-            r#"SELECT * FROM accounts WHERE id = ?"#,
-            contract.recipient_id
-        )
-        .fetch_one(db)
-        .await?;
-    
-        let mut recipient_address: Option<Address> = None;
-        let mut recipient_company_name: Option<String> = None;
-    
-        if recipient.company_id.is_some() {
-            let recipient_company = sqlx::query_as!(
-                Company,
-                r#"SELECT * FROM companies WHERE id = ?"#,
-                recipient.company_id
-            )
-            .fetch_one(db)
-            .await?;
-    
-            recipient_company_name = Some(recipient_company.name.clone());
-    
-            let result = sqlx::query_as!(
-                Address,
-                r#"SELECT * FROM address WHERE id = ?"#,
-                recipient_company.address_id
-            )
-            .fetch_one(db)
-            .await;
-    
-            recipient_address = match result {
-                Ok(address) => Some(address),
-                Err(_) => None,
-            };
-        }
-    
-        if recipient.address_id.is_some() && recipient_address.is_none() {
-            let result = sqlx::query_as!(
-                Address,
-                r#"SELECT * FROM address WHERE id = ?"#,
-                recipient.address_id
-            )
-            .fetch_one(db)
-            .await;
-    
-            recipient_address = match result {
-                Ok(address) => Some(address),
-                Err(_) => None,
-            };
+            contract_id
+        )
+        .fetch_one(db)
+        .await?;
+
+        if let Some(paused_date) = contract.paused_date.filter(|_| contract.is_paused()) {
+            return Err(anyhow::anyhow!(
+                "Contract {} is paused since {}, resume it before invoicing",
+                contract.id,
+                paused_date.format("%d-%m-%Y")
+            ));
         }
-    
-        let (recipient_street, recipient_postal_city) = match recipient_address {
-            Some(address) => (
-                format!(
-                    "{} {} {}",
-                    address.street.clone().unwrap_or("".to_string()),
-                    address.number.clone().unwrap_or("".to_string()),
-                    address.unit.clone().unwrap_or("".to_string()),
-                ),
-                format!(
-                    "{} {}",
-                    address.postalcode.clone().unwrap_or("".to_string()),
-                    address.city.clone().unwrap_or("".to_string()),
-                ),
-            ),
-            None => ("".to_string(), "".to_string()),
+
+        let sender_id = sender_id.unwrap_or(contract.sender_id);
+        currency = contract.currency.clone();
+        let (_, sender, _) = get_sender(db, sender_id).await?;
+        let recipient = get_account(db, contract.recipient_id).await?;
+        let recipient_vat_number = get_vat_number(db, &recipient).await?;
+        let reverse_charge =
+            is_reverse_charge(sender.vat_number.as_deref(), recipient_vat_number.as_deref());
+
+        let vat_percentage = match vat_percentage {
+            Some(vat_percentage) => vat_percentage,
+            None => default_vat_percentage()?,
         };
-        
-        let vat_amount = invoice.total_after_vat.unwrap_or(0)
-            - ((invoice.total_before_vat.unwrap_or(0) - invoice.discount.unwrap_or(0)) * 100);
-    
 
-        let invoice_template = self::invoice_maintenance::InvoiceMaintenanceTemplate {
-            sender_name: sender_account.name.clone().unwrap_or("".to_string()),
-            sender_company_name: sender.name.clone(),
-            sender_street: format!(
-                "{} {} {}",
-                sender_address.street.clone().unwrap_or("".to_string()),
-                sender_address.number.clone().unwrap_or("".to_string()),
-                sender_address.unit.clone().unwrap_or("".to_string()),
-            ),
-            sender_postal_city: format!(
-                "{} {}",
-                sender_address.postalcode.clone().unwrap_or("".to_string()),
-                sender_address.city.clone().unwrap_or("".to_string()),
-            ),
-            sender_phone: sender.phone.clone().unwrap_or("".to_string()),
-            sender_commerce_number: sender.commerce_number.clone().unwrap_or("".to_string()),
-            sender_vat_number: sender.vat_number.clone().unwrap_or("".to_string()),
-            recipient_name: recipient.name.clone().unwrap_or("".to_string()),
-            recipient_company_name: recipient_company_name.clone().unwrap_or("".to_string()),
-            recipient_street,
-            recipient_postal_city,
-            send_date: chrono::Local::now().format("%d-%m-%Y").to_string(),
-            contract_type: contract.contract_type.clone().unwrap_or("".to_string()),
-            invoice_number: invoice.invoice_number.clone(),
-            due_date: match invoice.payment_due_date {
-                Some(date) => date.format("%d-%m-%Y").to_string(),
-                None => "".to_string(),
-            },
-            invoice_table,
-            remarks: invoice.remarks.clone().unwrap_or("".to_string()),
-            currency_symbol: match invoice
-                .currency
-                .clone()
-                .unwrap_or("EUR".to_string())
-                .as_str()
-            {
-                "EUR" => "€".to_string(),
-                "USD" => "$".to_string(),
-                _ => "".to_string(),
+        // The next period starts where the last invoice of the contract ended. Invoices made
+        // before periods were stored continue from their send date
+        let last_contract_invoice = sqlx::query_as!(
+            Invoice,
+            r#"SELECT * FROM invoices WHERE contract_id = ? ORDER BY id DESC LIMIT 1"#,
+            contract.id
+        )
+        .fetch_optional(db)
+        .await?;
+        let first_period_start = match last_contract_invoice {
+            Some(last_invoice) => last_invoice
+                .period_end
+                .unwrap_or(last_invoice.send_date.unwrap_or(last_invoice.created_at)),
+            None => contract
+                .start_date
+                .ok_or(anyhow::anyhow!("Contract {} has no start date", contract.id))?,
+        };
+        let period_months = contract.invoice_period_months.unwrap_or(1).max(1);
+        let mut contract_end = contract.cancel_date.or(contract.end_date);
+
+        // The time a contract was paused isn't billed. Periods that began before the pause are
+        // invoiced first, the invoice after them continues from the resume date
+        let first_period_start = match (contract.paused_date, contract.resumed_date) {
+            (Some(paused_date), Some(resumed_date)) if first_period_start >= paused_date => {
+                first_period_start.max(resumed_date)
             }
-            .to_string(),
-            total_before_vat: format!(
-                "{:.2}",
-                invoice.total_before_vat.unwrap_or(0) as f64 / 100.0
-            ),
-            discount: format!("{:.2}", invoice.discount.unwrap_or(0) as f64 / 100.0),
-            vat_percentage: invoice.vat_percentage.unwrap_or(21).to_string(),
-            vat_amount: format!("{:.2}", vat_amount as f64 / 10000.0),
-            total_after_vat: format!(
-                "{:.2}",
-                invoice.total_after_vat.unwrap_or(0) as f64 / 10000.0
-            ),
+            (Some(paused_date), Some(_)) => {
+                contract_end = Some(contract_end.map_or(paused_date, |end| end.min(paused_date)));
+                first_period_start
+            }
+            _ => first_period_start,
         };
+        let periods = contract_periods(
+            first_period_start,
+            period_months as u32,
+            chrono::Local::now().naive_local(),
+            contract_end,
+        )?;
+
+        if periods.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Contract {} has nothing to invoice yet, its next period starts {}",
+                contract.id,
+                first_period_start.format("%d-%m-%Y")
+            ));
+        }
 
-        let pdf_args = PdfArgs {
-            template: "invoice-contract".to_string(),
-            data: PdfData::InvoiceMaintenance(&invoice_template),
+        for (start, end, billed_months) in &periods {
+            lines.push(DocumentLine {
+                title: period_description(*start, *end),
+                description: None,
+                quantity: *billed_months,
+                unit: "month".to_string(),
+                unit_price: contract.monthly_rate.unwrap_or(0),
+                total: (contract.monthly_rate.unwrap_or(0) as f64 * billed_months).round() as i64,
+                vat_percentage: if reverse_charge { 0 } else { vat_percentage },
+                task_id: None,
+                contract_id: Some(contract.id),
+                milestone: None,
+                rate_card: None,
+            });
+        }
+        let period_start = periods.first().map(|(start, _, _)| *start);
+        let period_end = periods.last().map(|(_, end, _)| *end);
+
+        let total_before_vat = lines.iter().map(|line: &DocumentLine| line.total).sum();
+        let line_totals = lines
+            .iter()
+            .map(|line| (line.total, line.vat_percentage))
+            .collect::<Vec<_>>();
+        let vat_breakdown = VatBreakdown::new(&line_totals, discount, &currency, reverse_charge);
+    
+        invoice = InvoiceCreateArgs {
+            sender_id,
+            recipient_id: contract.recipient_id,
+            invoice_number: String::new(),
+            send_date: Some(chrono::Utc::now().naive_local()),
+            quote_id: None,
+            payment_due_date: Some(
+                chrono::Utc::now()
+                    .checked_add_months(Months::new(1))
+                    .unwrap()
+                    .naive_local()),
+            payment_date: None,
+            contract_id: invoice_args.contract_id,
+            project_id: None,
+            remarks: invoice_args.remarks.clone(),
+            total_before_vat: Some(total_before_vat),
+            discount: Some(discount),
+            currency: Some(currency),
+            vat_percentage: vat_breakdown.vat_percentage(),
+            total_after_vat: Some(vat_breakdown.total_after_vat().amount),
+            invoice_url: None,
+            payment_request_url: None,
+            reverse_charge,
+            period_start,
+            period_end,
         };
-
-        generate_pdf(&pdf_args).await?
     } else {
-        let mut invoice_table = Vec::new();
         let mut project_id: Option<i64> = None;
+        let mut invoiced_quote = None;
 
         if invoice_args.quote_id.is_some() {
             let quote = sqlx::query_as!(
@@ -1558,21 +4036,16 @@ pub async fn make_invoice(db: &SqlitePool, invoice_args: &InvoiceMakeArgs) -> Re
             .fetch_one(db)
             .await?;
     
-            sender_id = quote.sender_id;
-    
-            if quote.vat_percentage.is_some() {
-                vat_percentage = quote.vat_percentage.unwrap();
-            }
+            sender_id = sender_id.or(Some(quote.sender_id));
     
-            if quote.discount.is_some() {
-                discount += quote.discount.unwrap_or(0);
-            }
+            vat_percentage = vat_percentage.or(quote.vat_percentage);
     
             if quote.project_id.is_some() {
                 project_id = quote.project_id;
             }
     
-            currency = quote.currency;
+            currency = quote.currency.clone();
+            invoiced_quote = Some(quote);
         }
     
         if invoice_args.project_id.is_some() {
@@ -1587,26 +4060,114 @@ pub async fn make_invoice(db: &SqlitePool, invoice_args: &InvoiceMakeArgs) -> Re
         .fetch_one(db)
         .await?;
     
-        let project_tasks = sqlx::query_as!(
-            ProjectTask,
-            r#"SELECT * FROM tasks WHERE project_id = ?"#,
+        // An instalment only bills the tasks of its milestone
+        let mut project_tasks = get_ordered_project_tasks(db, project.id).await?;
+        if let Some(milestone_id) = invoice_args.milestone_id {
+            check_task_milestone(db, project.id, milestone_id).await?;
+            project_tasks.retain(|task| task.milestone_id == Some(milestone_id));
+        }
+    
+        let sender_id = match sender_id {
+            Some(sender_id) => sender_id,
+            None => default_sender_id()?,
+        };
+        let (_, sender, _) = get_sender(db, sender_id).await?;
+        let recipient = get_account(db, project.client_id).await?;
+        let recipient_vat_number = get_vat_number(db, &recipient).await?;
+        let reverse_charge =
+            is_reverse_charge(sender.vat_number.as_deref(), recipient_vat_number.as_deref());
+        apply_rate_cards(db, &mut project_tasks, sender.id, project.client_id).await?;
+
+        let vat_percentage = match vat_percentage {
+            Some(vat_percentage) => vat_percentage,
+            None => default_vat_percentage()?,
+        };
+        // The time entries aren't billed yet, they are part of minutes_spent as well
+        time_entries = sqlx::query_as!(
+            TimeEntry,
+            r#"SELECT time_entries.* FROM time_entries
+            JOIN tasks ON tasks.id = time_entries.task_id
+            WHERE tasks.project_id = ?
+            AND time_entries.invoice_id IS NULL
+            AND time_entries.end_date IS NOT NULL"#,
             project_id
         )
         .fetch_all(db)
         .await?;
-    
-        let total_before_vat = project_tasks.iter().fold(0, |acc, task| {
-            acc + task
-                .minutes_spent
-                .unwrap_or(task.minutes_estimated.unwrap_or(0))
-                * task.minute_rate.unwrap_or(0)
+        time_entries.retain(|time_entry| {
+            project_tasks.iter().any(|task| task.task.id == time_entry.task_id)
         });
-        let total_after_vat = (total_before_vat - discount) * (100 + vat_percentage);
-    
+
+        // Only what isn't billed yet is invoiced, hourly tasks without minutes spent bill their
+        // estimate. Fixed prices and units don't depend on the time spent
+        for ordered_task in &project_tasks {
+            let task = &ordered_task.task;
+            let quantity = match task.pricing() {
+                TaskPricing::Hourly if invoice_args.time_entries => time_entries
+                    .iter()
+                    .filter(|time_entry| time_entry.task_id == task.id)
+                    .map(|time_entry| time_entry.minutes)
+                    .sum::<i64>() as f64,
+                TaskPricing::Hourly => {
+                    let minutes = task.minutes_spent.unwrap_or(task.minutes_estimated.unwrap_or(0));
+                    (minutes - task.minutes_billed.unwrap_or(0)).max(0) as f64
+                }
+                TaskPricing::Fixed => (1.0 - task.quantity_billed.unwrap_or(0.0)).max(0.0),
+                TaskPricing::PerUnit => {
+                    (task.quantity.unwrap_or(0.0) - task.quantity_billed.unwrap_or(0.0)).max(0.0)
+                }
+            };
+            if quantity <= 0.0 {
+                continue;
+            }
+
+            let vat_percentage = if reverse_charge {
+                0
+            } else {
+                task.vat_percentage.unwrap_or(vat_percentage)
+            };
+            lines.push(DocumentLine::from_task(ordered_task, quantity, vat_percentage));
+            match task.pricing() {
+                TaskPricing::Hourly => billed_minutes.push((
+                    task.id,
+                    task.minutes_billed.unwrap_or(0),
+                    quantity as i64,
+                )),
+                TaskPricing::Fixed | TaskPricing::PerUnit => billed_quantities.push((
+                    task.id,
+                    task.quantity_billed.unwrap_or(0.0),
+                    quantity,
+                )),
+            }
+        }
+
+        if lines.is_empty() {
+            return Err(match invoice_args.milestone_id {
+                Some(milestone_id) => anyhow::anyhow!(
+                    "Milestone {milestone_id} of project {} has nothing that isn't billed yet",
+                    project.id
+                ),
+                None => anyhow::anyhow!(
+                    "Project {} has nothing that isn't billed yet",
+                    project.id
+                ),
+            });
+        }
+        let total_before_vat = lines.iter().map(|line| line.total).sum();
+        if let Some(quote) = &invoiced_quote {
+            quote_discount = quote_discount_share(db, quote, total_before_vat).await?;
+            discount += quote_discount;
+        }
+        let line_totals = lines
+            .iter()
+            .map(|line| (line.total, line.vat_percentage))
+            .collect::<Vec<_>>();
+        let vat_breakdown = VatBreakdown::new(&line_totals, discount, &currency, reverse_charge);
+
         invoice = InvoiceCreateArgs {
             sender_id,
             recipient_id: project.client_id,
-            invoice_number: format!("{}{:05}", chrono::Utc::now().year(), project.id),
+            invoice_number: String::new(),
             send_date: Some(chrono::Utc::now().naive_local()),
             quote_id: invoice_args.quote_id,
             payment_due_date: Some(
@@ -1621,243 +4182,703 @@ pub async fn make_invoice(db: &SqlitePool, invoice_args: &InvoiceMakeArgs) -> Re
             total_before_vat: Some(total_before_vat),
             discount: Some(discount),
             currency: Some(currency),
-            vat_percentage: Some(vat_percentage),
-            total_after_vat: Some(total_after_vat),
+            vat_percentage: vat_breakdown.vat_percentage(),
+            total_after_vat: Some(vat_breakdown.total_after_vat().amount),
             invoice_url: None,
             payment_request_url: None,
+            reverse_charge,
+            period_start: None,
+            period_end: None,
         };
-    
-        let sender_account = sqlx::query_as!(
-            Account,
-            r#"SELECT * FROM accounts WHERE id = ?"#,
-            invoice.sender_id
-        )
-        .fetch_one(db)
-        .await?;
-    
-        let sender = sqlx::query_as!(
-            Company,
-            r#"SELECT * FROM companies WHERE id = ?"#,
-            sender_account.company_id
+    }
+
+    Ok(InvoiceDraft {
+        invoice,
+        quote_discount,
+        lines,
+        billed_minutes,
+        billed_quantities,
+        time_entries,
+    })
+}
+
+pub async fn make_invoice(db: &SqlitePool, invoice_args: &InvoiceMakeArgs) -> Result<String> {
+    let InvoiceDraft {
+        mut invoice,
+        quote_discount,
+        lines,
+        billed_minutes,
+        billed_quantities,
+        time_entries,
+    } = prepare_invoice(db, invoice_args).await?;
+
+    // The invoice is committed before its pdf is rendered, a failed pdf can be rendered again
+    // with render-invoice
+    let mut tx = db.begin().await?;
+    invoice.invoice_number =
+        allocate_document_number(&mut tx, invoice.sender_id, SequenceDocument::Invoice).await?;
+
+    let result = sqlx::query!(
+        r#"
+INSERT INTO invoices (
+    sender_id,
+    recipient_id,
+    invoice_number,
+    quote_id,
+    payment_due_date,
+    payment_date,
+    contract_id,
+    project_id,
+    remarks,
+    total_before_vat,
+    discount,
+    vat_percentage,
+    currency,
+    total_after_vat,
+    payment_request_url,
+    reverse_charge,
+    period_start,
+    period_end,
+    quote_discount
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#,
+        invoice.sender_id,
+        invoice.recipient_id,
+        invoice.invoice_number,
+        invoice.quote_id,
+        invoice.payment_due_date,
+        invoice.payment_date,
+        invoice.contract_id,
+        invoice.project_id,
+        invoice.remarks,
+        invoice.total_before_vat,
+        invoice.discount,
+        invoice.vat_percentage,
+        invoice.currency,
+        invoice.total_after_vat,
+        invoice.payment_request_url,
+        invoice.reverse_charge,
+        invoice.period_start,
+        invoice.period_end,
+        quote_discount
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::anyhow!("Failed to insert invoice"));
+    }
+
+    let invoice_id = result.last_insert_rowid();
+    add_invoice_lines(&mut tx, invoice_id, &lines).await?;
+
+    // The billed minutes and quantities are only raised from what the draft read, a task another
+    // invoice billed in the meantime isn't billed twice
+    for (task_id, billed, minutes) in &billed_minutes {
+        let result = sqlx::query!(
+            r#"UPDATE tasks SET minutes_billed = COALESCE(minutes_billed, 0) + ?
+            WHERE id = ? AND COALESCE(minutes_billed, 0) = ?"#,
+            minutes,
+            task_id,
+            billed
         )
-        .fetch_one(db)
+        .execute(&mut *tx)
         .await?;
-    
-        let sender_address = sqlx::query_as!(
-            Address,
-            r#"SELECT * FROM address WHERE id = ?"#,
-            sender.address_id
+
+        if result.rows_affected() != 1 {
+            return Err(anyhow::anyhow!(
+                "Task {task_id} was billed in the meantime, make the invoice again"
+            ));
+        }
+    }
+    for (task_id, billed, quantity) in &billed_quantities {
+        let result = sqlx::query!(
+            r#"UPDATE tasks SET quantity_billed = COALESCE(quantity_billed, 0) + ?
+            WHERE id = ? AND COALESCE(quantity_billed, 0) = ?"#,
+            quantity,
+            task_id,
+            billed
         )
-        .fetch_one(db)
+        .execute(&mut *tx)
         .await?;
-    
-        let recipient = sqlx::query_as!(
-            Account,
-            r#"SELECT * FROM accounts WHERE id = ?"#,
-            project.client_id
+
+        if result.rows_affected() != 1 {
+            return Err(anyhow::anyhow!(
+                "Task {task_id} was billed in the meantime, make the invoice again"
+            ));
+        }
+    }
+    // A time entry that another invoice billed in the meantime isn't billed twice
+    for time_entry in &time_entries {
+        let result = sqlx::query!(
+            r#"UPDATE time_entries SET invoice_id = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ? AND invoice_id IS NULL"#,
+            invoice_id,
+            time_entry.id
         )
-        .fetch_one(db)
+        .execute(&mut *tx)
         .await?;
-    
-        let mut recipient_address: Option<Address> = None;
-        let mut recipient_company_name: Option<String> = None;
-    
-        if recipient.company_id.is_some() {
-            let recipient_company = sqlx::query_as!(
-                Company,
-                r#"SELECT * FROM companies WHERE id = ?"#,
-                recipient.company_id
-            )
-            .fetch_one(db)
-            .await?;
-    
-            recipient_company_name = Some(recipient_company.name.clone());
-    
-            let result = sqlx::query_as!(
-                Address,
-                r#"SELECT * FROM address WHERE id = ?"#,
-                recipient_company.address_id
-            )
-            .fetch_one(db)
-            .await;
-    
-            recipient_address = match result {
-                Ok(address) => Some(address),
-                Err(_) => None,
-            };
-        }
-    
-        if recipient.address_id.is_some() && recipient_address.is_none() {
-            let result = sqlx::query_as!(
-                Address,
-                r#"SELECT * FROM address WHERE id = ?"#,
-                recipient.address_id
-            )
-            .fetch_one(db)
-            .await;
-    
-            recipient_address = match result {
-                Ok(address) => Some(address),
-                Err(_) => None,
-            };
+
+        if result.rows_affected() != 1 {
+            return Err(anyhow::anyhow!(
+                "Time entry {} is billed already, make the invoice again",
+                time_entry.id
+            ));
         }
-    
-        let (recipient_street, recipient_postal_city) = match recipient_address {
-            Some(address) => (
-                format!(
-                    "{} {} {}",
-                    address.street.clone().unwrap_or("".to_string()),
-                    address.number.clone().unwrap_or("".to_string()),
-                    address.unit.clone().unwrap_or("".to_string()),
-                ),
-                format!(
-                    "{} {}",
-                    address.postalcode.clone().unwrap_or("".to_string()),
-                    address.city.clone().unwrap_or("".to_string()),
-                ),
-            ),
-            None => ("".to_string(), "".to_string()),
+    }
+
+    tx.commit().await?;
+
+    render_invoice(db, invoice_id).await
+}
+
+/// The lines make-invoice would bill, nothing is stored
+pub async fn preview_invoice(
+    db: &SqlitePool,
+    invoice_args: &InvoiceMakeArgs,
+) -> Result<Vec<DocumentLine>> {
+    let draft = prepare_invoice(db, invoice_args).await?;
+
+    Ok(draft.lines)
+}
+
+/// Render the pdf of a stored invoice again from its lines and update the invoice_url
+pub async fn render_invoice(db: &SqlitePool, id: i64) -> Result<String> {
+    let invoice = get_invoice(db, id).await?;
+    let lines = get_invoice_lines(db, id).await?;
+    let invoice_url = render_invoice_pdf(db, &invoice, &lines).await?;
+
+    sqlx::query!(r#"UPDATE invoices SET invoice_url = ? WHERE id = ?"#, invoice_url, id)
+        .execute(db)
+        .await?;
+
+    Ok(invoice_url)
+}
+
+// Like quotes the invoice pdf only uses the stored invoice and lines. Contract invoices use the
+// maintenance template, the others the project template
+async fn render_invoice_pdf(
+    db: &SqlitePool,
+    invoice: &Invoice,
+    lines: &[InvoiceLine],
+) -> Result<String> {
+    if lines.is_empty() && invoice.total_before_vat != 0 {
+        return Err(anyhow::anyhow!(
+            "Invoice {} was made before its lines were stored and can't be rendered, credit it and make a new invoice",
+            invoice.id
+        ));
+    }
+
+    let (sender_account, sender, sender_address) = get_sender(db, invoice.sender_id).await?;
+    let (recipient, recipient_company_name, recipient_address) =
+        get_recipient(db, invoice.recipient_id).await?;
+    let recipient_vat_number = get_vat_number(db, &recipient).await?;
+
+    let line_totals = lines
+        .iter()
+        .map(|line| (line.total, line.vat_percentage))
+        .collect::<Vec<_>>();
+    let vat_breakdown = VatBreakdown::new(
+        &line_totals,
+        invoice.discount.unwrap_or(0),
+        &invoice.currency,
+        invoice.reverse_charge,
+    );
+    let total_after_vat = vat_breakdown.total_after_vat();
+
+    let (sender_street, sender_postal_city) = address_lines(Some(&sender_address));
+    let (recipient_street, recipient_postal_city) = address_lines(recipient_address.as_ref());
+    let send_date = invoice
+        .send_date
+        .unwrap_or(invoice.created_at)
+        .format("%d-%m-%Y")
+        .to_string();
+    let due_date = match invoice.payment_due_date {
+        Some(date) => date.format("%d-%m-%Y").to_string(),
+        None => "".to_string(),
+    };
+
+    if let Some(contract_id) = invoice.contract_id {
+        let contract = get_contract(db, contract_id).await?;
+        let invoice_table = lines
+            .iter()
+            .map(|line| self::invoice_maintenance::InvoiceMaintenanceTableData {
+                description: line.title.clone(),
+                months: quantity_columns(line.quantity, &line.unit).0,
+                monthly_rate: invoice.money(line.unit_price).format_amount(),
+                total: invoice.money(line.total).format_amount(),
+            })
+            .collect();
+
+        let invoice_template = self::invoice_maintenance::InvoiceMaintenanceTemplate {
+            sender_name: sender_account.name.clone().unwrap_or("".to_string()),
+            sender_company_name: sender.name.clone(),
+            sender_street,
+            sender_postal_city,
+            sender_phone: sender.phone.clone().unwrap_or("".to_string()),
+            sender_commerce_number: sender.commerce_number.clone().unwrap_or("".to_string()),
+            sender_vat_number: sender.vat_number.clone().unwrap_or("".to_string()),
+            sender_iban: sender.iban.clone().unwrap_or("".to_string()),
+            recipient_name: recipient.name.clone().unwrap_or("".to_string()),
+            recipient_company_name: recipient_company_name.unwrap_or("".to_string()),
+            recipient_street,
+            recipient_postal_city,
+            send_date,
+            contract_type: contract.contract_type.clone().unwrap_or("".to_string()),
+            invoice_number: invoice.invoice_number.clone(),
+            due_date,
+            invoice_table,
+            remarks: invoice.remarks.clone().unwrap_or("".to_string()),
+            currency_symbol: total_after_vat.symbol(),
+            total_before_vat: invoice.money(invoice.total_before_vat).format_amount(),
+            discount: invoice.money(invoice.discount.unwrap_or(0)).format_amount(),
+            vat_lines: vat_table(&vat_breakdown),
+            vat_note: vat_note(&vat_breakdown, recipient_vat_number.as_deref()),
+            vat_amount: vat_breakdown.vat().format_amount(),
+            total_after_vat: total_after_vat.format_amount(),
         };
-    
-        let vat_amount = invoice.total_after_vat.unwrap_or(0)
-            - ((invoice.total_before_vat.unwrap_or(0) - invoice.discount.unwrap_or(0)) * 100);
-    
 
-        for project_task in project_tasks {
-            invoice_table.push(self::invoice::InvoiceTableData {
-                title: project_task.title.clone(),
-                description: project_task.description.clone().unwrap_or("".to_string()),
-                hours_spent: project_task
-                    .minutes_spent
-                    .unwrap_or(project_task.minutes_estimated.unwrap_or(0))
-                    as f64
-                    / 60.0,
-                hourly_rate: format!(
-                    "{:.2}",
-                    (project_task.minute_rate.unwrap_or(0) * 60) as f64 / 100.0
-                ),
-                total: format!(
-                    "{:.2}",
-                    (project_task
-                        .minutes_spent
-                        .unwrap_or(project_task.minutes_estimated.unwrap_or(0))
-                        * project_task.minute_rate.unwrap_or(0)) as f64
-                        / 100.0
-                ),
-            });
+        let pdf_args = PdfArgs {
+            template: "invoice-contract".to_string(),
+            data: PdfData::InvoiceMaintenance(&invoice_template),
+        };
+
+        return generate_pdf(&pdf_args).await;
+    }
+
+    let project_title = match invoice.project_id {
+        Some(project_id) => get_project(db, project_id).await?.title,
+        None => "".to_string(),
+    };
+    let invoice_table = with_milestone_subtotals(
+        lines,
+        |line| line.milestone.as_deref(),
+        |line| {
+            let (quantity, unit) = quantity_columns(line.quantity, &line.unit);
+            self::invoice::InvoiceTableData {
+                title: line.title.clone(),
+                description: line.description.clone().unwrap_or("".to_string()),
+                quantity,
+                unit,
+                unit_price: match line.unit.as_str() {
+                    "fixed" => "".to_string(),
+                    _ => invoice.money(line.unit_price).format_amount(),
+                },
+                total: invoice.money(line.total).format_amount(),
+            }
+        },
+        |milestone, group| {
+            let (quantity, unit) = subtotal_quantity_columns(
+                &group.iter().map(|line| (line.quantity, line.unit.as_str())).collect::<Vec<_>>(),
+            );
+            self::invoice::InvoiceTableData {
+                title: format!("Subtotaal {milestone}"),
+                description: "".to_string(),
+                quantity,
+                unit,
+                unit_price: "".to_string(),
+                total: invoice.money(group.iter().map(|line| line.total).sum()).format_amount(),
+            }
+        },
+    );
+
+    let invoice_template = self::invoice::InvoiceTemplate {
+        sender_name: sender_account.name.clone().unwrap_or("".to_string()),
+        sender_company_name: sender.name.clone(),
+        sender_street,
+        sender_postal_city,
+        sender_phone: sender.phone.clone().unwrap_or("".to_string()),
+        sender_commerce_number: sender.commerce_number.clone().unwrap_or("".to_string()),
+        sender_vat_number: sender.vat_number.clone().unwrap_or("".to_string()),
+        sender_iban: sender.iban.clone().unwrap_or("".to_string()),
+        recipient_name: recipient.name.clone().unwrap_or("".to_string()),
+        recipient_company_name: recipient_company_name.unwrap_or("".to_string()),
+        recipient_street,
+        recipient_postal_city,
+        send_date,
+        project_title,
+        invoice_number: invoice.invoice_number.clone(),
+        due_date,
+        project_tasks: invoice_table,
+        remarks: invoice.remarks.clone().unwrap_or("".to_string()),
+        currency_symbol: total_after_vat.symbol(),
+        total_before_vat: invoice.money(invoice.total_before_vat).format_amount(),
+        discount: invoice.money(invoice.discount.unwrap_or(0)).format_amount(),
+        vat_lines: vat_table(&vat_breakdown),
+        vat_note: vat_note(&vat_breakdown, recipient_vat_number.as_deref()),
+        vat_amount: vat_breakdown.vat().format_amount(),
+        total_after_vat: total_after_vat.format_amount(),
+    };
+
+    let pdf_args = PdfArgs {
+        template: "invoice-project".to_string(),
+        data: PdfData::Invoice(&invoice_template),
+    };
+
+    generate_pdf(&pdf_args).await
+}
+
+// The recipient with the company name and address printed on documents, the company address
+// goes before the account address
+async fn get_recipient(
+    db: &SqlitePool,
+    recipient_id: i64,
+) -> Result<(Account, Option<String>, Option<Address>)> {
+    let recipient = get_account(db, recipient_id).await?;
+    let mut company_name = None;
+    let mut address = None;
+
+    if let Some(company_id) = recipient.company_id {
+        let company = get_company(db, company_id).await?;
+        company_name = Some(company.name);
+        if let Some(address_id) = company.address_id {
+            address = get_address(db, address_id).await.ok();
+        }
+    }
+
+    if address.is_none() {
+        if let Some(address_id) = recipient.address_id {
+            address = get_address(db, address_id).await.ok();
         }
+    }
 
-        let invoice_template = self::invoice::InvoiceTemplate {
-            sender_name: sender_account.name.clone().unwrap_or("".to_string()),
-            sender_company_name: sender.name.clone(),
-            sender_street: format!(
-                "{} {} {}",
-                sender_address.street.clone().unwrap_or("".to_string()),
-                sender_address.number.clone().unwrap_or("".to_string()),
-                sender_address.unit.clone().unwrap_or("".to_string()),
-            ),
-            sender_postal_city: format!(
-                "{} {}",
-                sender_address.postalcode.clone().unwrap_or("".to_string()),
-                sender_address.city.clone().unwrap_or("".to_string()),
-            ),
-            sender_phone: sender.phone.clone().unwrap_or("".to_string()),
-            sender_commerce_number: sender.commerce_number.clone().unwrap_or("".to_string()),
-            sender_vat_number: sender.vat_number.clone().unwrap_or("".to_string()),
-            recipient_name: recipient.name.clone().unwrap_or("".to_string()),
-            recipient_company_name: recipient_company_name.clone().unwrap_or("".to_string()),
-            recipient_street,
-            recipient_postal_city,
-            send_date: chrono::Local::now().format("%d-%m-%Y").to_string(),
-            project_title: project.title.clone(),
-            invoice_number: invoice.invoice_number.clone(),
-            due_date: match invoice.payment_due_date {
-                Some(date) => date.format("%d-%m-%Y").to_string(),
-                None => "".to_string(),
-            },
-            project_tasks: invoice_table,
-            remarks: invoice.remarks.clone().unwrap_or("".to_string()),
-            currency_symbol: match invoice
-                .currency
-                .clone()
-                .unwrap_or("EUR".to_string())
-                .as_str()
-            {
-                "EUR" => "€".to_string(),
-                "USD" => "$".to_string(),
-                _ => "".to_string(),
+    Ok((recipient, company_name, address))
+}
+
+pub async fn get_credit_note(db: &SqlitePool, id: i64) -> Result<CreditNote> {
+    sqlx::query_as!(CreditNote, r#"SELECT * FROM credit_notes WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+pub async fn get_credit_notes(db: &SqlitePool, invoice_id: Option<i64>) -> Result<Vec<CreditNote>> {
+    sqlx::query_as!(
+        CreditNote,
+        r#"SELECT * FROM credit_notes WHERE ($1 IS NULL OR invoice_id = $1)"#,
+        invoice_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+// What is left to credit of an invoice, one line per VAT rate after its discount. Earlier credit
+// notes lower every rate in proportion, the last rate takes what is left after VAT
+async fn full_credit_lines(db: &SqlitePool, invoice: &Invoice) -> Result<Vec<CreditLine>> {
+    if invoice.amount_due() <= 0 {
+        return Err(anyhow::anyhow!(
+            "Invoice {} is credited in full already",
+            invoice.invoice_number
+        ));
+    }
+
+    let description = format!("Creditering van factuur {}", invoice.invoice_number);
+    let invoice_lines = get_invoice_lines(db, invoice.id).await?;
+
+    // Invoices made before their lines were stored are credited at their own rate
+    if invoice_lines.is_empty() {
+        return Ok(vec![CreditLine {
+            description,
+            amount: invoice.total_before_vat - invoice.discount.unwrap_or(0),
+            vat_percentage: None,
+        }]);
+    }
+
+    let line_totals = invoice_lines
+        .iter()
+        .map(|line| (line.total, line.vat_percentage))
+        .collect::<Vec<_>>();
+    let vat_breakdown = VatBreakdown::new(
+        &line_totals,
+        invoice.discount.unwrap_or(0),
+        &invoice.currency,
+        invoice.reverse_charge,
+    );
+    let rate_count = vat_breakdown.lines.len();
+
+    let mut left_after_vat = invoice.amount_due();
+    let mut lines = Vec::new();
+    for (i, vat_line) in vat_breakdown.lines.iter().enumerate() {
+        let vat_percentage = vat_line.vat_percentage;
+        let amount = if invoice.credited_total == 0 {
+            vat_line.base.amount
+        } else if i + 1 < rate_count {
+            (vat_line.base.amount as i128 * invoice.amount_due() as i128
+                / invoice.total_after_vat as i128) as i64
+        } else {
+            let with_vat = |amount: i64| invoice.money(amount).with_vat(vat_percentage).amount;
+            let mut amount =
+                (left_after_vat * 100 + 99 + vat_percentage) / (100 + vat_percentage);
+            while amount > 0 && with_vat(amount) > left_after_vat {
+                amount -= 1;
             }
-            .to_string(),
-            total_before_vat: format!(
-                "{:.2}",
-                invoice.total_before_vat.unwrap_or(0) as f64 / 100.0
-            ),
-            discount: format!("{:.2}", invoice.discount.unwrap_or(0) as f64 / 100.0),
-            vat_percentage: invoice.vat_percentage.unwrap_or(21).to_string(),
-            vat_amount: format!("{:.2}", vat_amount as f64 / 10000.0),
-            total_after_vat: format!(
-                "{:.2}",
-                invoice.total_after_vat.unwrap_or(0) as f64 / 10000.0
-            ),
+            amount
         };
+        left_after_vat -= invoice.money(amount).with_vat(vat_percentage).amount;
 
-        let pdf_args = PdfArgs {
-            template: "invoice-project".to_string(),
-            data: PdfData::Invoice(&invoice_template),
-        };
+        if amount > 0 {
+            lines.push(CreditLine {
+                description: if rate_count > 1 {
+                    format!("{description} ({vat_percentage}% btw)")
+                } else {
+                    description.clone()
+                },
+                amount,
+                vat_percentage: Some(vat_percentage),
+            });
+        }
+    }
 
-        generate_pdf(&pdf_args).await?
+    Ok(lines)
+}
+
+pub async fn make_credit_note(db: &SqlitePool, credit_note_args: &CreditNoteMakeArgs) -> Result<String> {
+    let invoice = get_invoice(db, credit_note_args.invoice_id).await?;
+
+    let lines = if credit_note_args.line.is_empty() {
+        full_credit_lines(db, &invoice).await?
+    } else {
+        credit_note_args.line.clone()
+    };
+
+    // Credit notes are stored negative, the invoice keeps the credited total positive
+    let credited_lines = lines
+        .iter()
+        .map(|line| {
+            let vat_percentage = line.vat_percentage.or(invoice.vat_percentage).ok_or(
+                anyhow::anyhow!(
+                    "Invoice {} has more than one VAT rate, give the rate of \"{}\" as \"description=amount@rate\"",
+                    invoice.invoice_number,
+                    line.description
+                ),
+            )?;
+            Ok((-line.amount, vat_percentage))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let vat_breakdown =
+        VatBreakdown::new(&credited_lines, 0, &invoice.currency, invoice.reverse_charge);
+    let total_after_vat = vat_breakdown.total_after_vat();
+    let creditable = invoice.money(invoice.amount_due());
+    if -total_after_vat.amount > creditable.amount {
+        return Err(anyhow::anyhow!(
+            "Invoice {} has {} left to credit, the lines add up to {}",
+            invoice.invoice_number,
+            creditable,
+            -total_after_vat
+        ));
+    }
+
+    // The credit note is committed before its pdf is rendered, so a failed pdf doesn't leave a
+    // document behind with a number that was never stored
+    let mut tx = db.begin().await?;
+    let credit_note_number =
+        allocate_document_number(&mut tx, invoice.sender_id, SequenceDocument::CreditNote).await?;
+
+    let send_date = chrono::Local::now().naive_local();
+    let total_before_vat = vat_breakdown.total_before_vat().amount;
+    let vat_percentage = vat_breakdown.vat_percentage();
+    // The lines are credited after the discount of the invoice, the report nets the share of the
+    // discount they reverse against the discount of the invoice
+    let invoice_discount = invoice.discount.unwrap_or(0);
+    let invoiced_after_discount = invoice.total_before_vat - invoice_discount;
+    let discount = if invoiced_after_discount > 0 {
+        (invoice_discount as f64 * total_before_vat as f64 / invoiced_after_discount as f64).round()
+            as i64
+    } else {
+        0
     };
-    
     let result = sqlx::query!(
         r#"
-INSERT INTO invoices (
+INSERT INTO credit_notes (
+    invoice_id,
     sender_id,
     recipient_id,
-    invoice_number,
-    quote_id,
-    payment_due_date,
-    payment_date,
-    contract_id,
-    project_id,
+    credit_note_number,
+    send_date,
     remarks,
     total_before_vat,
-    discount,
     vat_percentage,
     currency,
     total_after_vat,
-    invoice_url,
-    payment_request_url
-) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    reverse_charge,
+    discount
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 "#,
+        invoice.id,
         invoice.sender_id,
         invoice.recipient_id,
-        invoice.invoice_number,
-        invoice.quote_id,
-        invoice.payment_due_date,
-        invoice.payment_date,
-        invoice.contract_id,
-        invoice.project_id,
-        invoice.remarks,
-        invoice.total_before_vat,
-        invoice.discount,
-        invoice.vat_percentage,
+        credit_note_number,
+        send_date,
+        credit_note_args.remarks,
+        total_before_vat,
+        vat_percentage,
         invoice.currency,
-        invoice.total_after_vat,
-        invoice_url,
-        invoice.payment_request_url
+        total_after_vat.amount,
+        invoice.reverse_charge,
+        discount
     )
-    .execute(db)
+    .execute(&mut *tx)
     .await?;
 
     if result.rows_affected() == 0 {
-        return Err(anyhow::anyhow!("Failed to insert invoice"));
+        return Err(anyhow::anyhow!("Failed to insert credit note"));
     }
 
-    Ok(invoice_url)
+    let credit_note_id = result.last_insert_rowid();
+    for (line, (amount, vat_percentage)) in lines.iter().zip(credited_lines.iter()) {
+        sqlx::query!(
+            r#"INSERT INTO credit_note_lines (credit_note_id, description, amount, vat_percentage) VALUES (?, ?, ?, ?)"#,
+            credit_note_id,
+            line.description,
+            amount,
+            vat_percentage
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Checked again by the update, so two credit notes at the same time can't credit more than
+    // the invoice
+    let credited = -total_after_vat.amount;
+    let result = sqlx::query!(
+        r#"UPDATE invoices SET credited_total = credited_total + ?
+        WHERE id = ? AND credited_total + ? <= total_after_vat"#,
+        credited,
+        invoice.id,
+        credited
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::anyhow!(
+            "Invoice {} changed while crediting it, it has less than {} left to credit",
+            invoice.invoice_number,
+            -total_after_vat
+        ));
+    }
+
+    tx.commit().await?;
+
+    render_credit_note(db, credit_note_id).await
+}
+
+pub async fn get_credit_note_lines(db: &SqlitePool, credit_note_id: i64) -> Result<Vec<CreditNoteLine>> {
+    sqlx::query_as!(
+        CreditNoteLine,
+        r#"SELECT * FROM credit_note_lines WHERE credit_note_id = ? ORDER BY id"#,
+        credit_note_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+/// Render the pdf of a stored credit note again from its lines and update the credit_note_url
+pub async fn render_credit_note(db: &SqlitePool, id: i64) -> Result<String> {
+    let credit_note = get_credit_note(db, id).await?;
+    let lines = get_credit_note_lines(db, id).await?;
+    let credit_note_url = render_credit_note_pdf(db, &credit_note, &lines).await?;
+
+    sqlx::query!(
+        r#"UPDATE credit_notes SET credit_note_url = ? WHERE id = ?"#,
+        credit_note_url,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(credit_note_url)
+}
+
+// Like invoices the credit note pdf only uses the stored credit note and lines, which are
+// negative and keep their own VAT rate
+async fn render_credit_note_pdf(
+    db: &SqlitePool,
+    credit_note: &CreditNote,
+    lines: &[CreditNoteLine],
+) -> Result<String> {
+    let invoice = get_invoice(db, credit_note.invoice_id).await?;
+    let (sender_account, sender, sender_address) = get_sender(db, credit_note.sender_id).await?;
+    let (recipient, recipient_company_name, recipient_address) =
+        get_recipient(db, credit_note.recipient_id).await?;
+    let recipient_vat_number = get_vat_number(db, &recipient).await?;
+
+    let line_totals = lines
+        .iter()
+        .map(|line| (line.amount, line.vat_percentage))
+        .collect::<Vec<_>>();
+    let vat_breakdown = VatBreakdown::new(
+        &line_totals,
+        0,
+        &credit_note.currency,
+        credit_note.reverse_charge,
+    );
+    let total_after_vat = vat_breakdown.total_after_vat();
+
+    let (sender_street, sender_postal_city) = address_lines(Some(&sender_address));
+    let (recipient_street, recipient_postal_city) = address_lines(recipient_address.as_ref());
+
+    let credit_note_template = self::credit_note::CreditNoteTemplate {
+        sender_name: sender_account.name.clone().unwrap_or("".to_string()),
+        sender_company_name: sender.name.clone(),
+        sender_street,
+        sender_postal_city,
+        sender_phone: sender.phone.clone().unwrap_or("".to_string()),
+        sender_commerce_number: sender.commerce_number.clone().unwrap_or("".to_string()),
+        sender_vat_number: sender.vat_number.clone().unwrap_or("".to_string()),
+        sender_iban: sender.iban.clone().unwrap_or("".to_string()),
+        recipient_name: recipient.name.clone().unwrap_or("".to_string()),
+        recipient_company_name: recipient_company_name.unwrap_or("".to_string()),
+        recipient_street,
+        recipient_postal_city,
+        send_date: credit_note
+            .send_date
+            .map_or("".to_string(), |date| date.format("%d-%m-%Y").to_string()),
+        credit_note_number: credit_note.credit_note_number.clone(),
+        invoice_number: invoice.invoice_number.clone(),
+        invoice_date: invoice
+            .send_date
+            .map_or("".to_string(), |date| date.format("%d-%m-%Y").to_string()),
+        credit_lines: lines
+            .iter()
+            .map(|line| self::credit_note::CreditNoteTableData {
+                description: line.description.clone(),
+                total: credit_note.money(line.amount).format_amount(),
+            })
+            .collect(),
+        remarks: credit_note.remarks.clone().unwrap_or("".to_string()),
+        currency_symbol: total_after_vat.symbol(),
+        total_before_vat: vat_breakdown.total_before_vat().format_amount(),
+        vat_lines: vat_table(&vat_breakdown),
+        vat_note: vat_note(&vat_breakdown, recipient_vat_number.as_deref()),
+        vat_amount: vat_breakdown.vat().format_amount(),
+        total_after_vat: total_after_vat.format_amount(),
+    };
+
+    let pdf_args = PdfArgs {
+        template: "credit-note".to_string(),
+        data: PdfData::CreditNote(&credit_note_template),
+    };
+
+    generate_pdf(&pdf_args).await
 }
 
 pub async fn update_invoice(db: &SqlitePool, id: i64, invoice: &InvoiceUpdateArgs) -> Result<u64> {
+    if let Some(invoice_number) = &invoice.invoice_number {
+        let sender_id = match invoice.sender_id {
+            Some(sender_id) => sender_id,
+            None => get_invoice(db, id).await?.sender_id,
+        };
+
+        if invoice_number_exists(db, sender_id, invoice_number, Some(id)).await? {
+            return Err(anyhow::anyhow!(
+                "Invoice number {invoice_number} is already used by this sender"
+            ));
+        }
+    }
+
     let result = sqlx::query!(
         r#"UPDATE invoices SET
     sender_id = ?,
@@ -1875,7 +4896,8 @@ pub async fn update_invoice(db: &SqlitePool, id: i64, invoice: &InvoiceUpdateArg
     currency = ?,
     total_after_vat = ?,
     invoice_url = ?,
-    payment_request_url = ?
+    payment_request_url = ?,
+    reverse_charge = COALESCE(?, reverse_charge)
 WHERE id = ?"#,
         invoice.sender_id,
         invoice.recipient_id,
@@ -1893,6 +4915,7 @@ WHERE id = ?"#,
         invoice.total_after_vat,
         invoice.invoice_url,
         invoice.payment_request_url,
+        invoice.reverse_charge,
         id
     )
     .execute(db)
@@ -1905,6 +4928,115 @@ WHERE id = ?"#,
     Ok(result.rows_affected())
 }
 
+pub async fn get_payment(db: &SqlitePool, id: i64) -> Result<Payment> {
+    sqlx::query_as!(Payment, r#"SELECT * FROM payments WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+pub async fn get_payments(db: &SqlitePool, invoice_id: i64) -> Result<Vec<Payment>> {
+    sqlx::query_as!(
+        Payment,
+        r#"SELECT * FROM payments WHERE invoice_id = ? ORDER BY date"#,
+        invoice_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(anyhow::Error::msg)
+}
+
+pub async fn pay_invoice(
+    db: &SqlitePool,
+    invoice_id: i64,
+    payment: &PaymentCreateArgs,
+) -> Result<Payment> {
+    let invoice = get_invoice(db, invoice_id).await?;
+    let outstanding = invoice.amount_due() - invoice.paid_total;
+
+    match invoice.status() {
+        InvoiceStatus::Paid => {
+            return Err(anyhow::anyhow!("Invoice {} is already paid", invoice.invoice_number))
+        }
+        InvoiceStatus::WrittenOff => {
+            return Err(anyhow::anyhow!("Invoice {} is written off", invoice.invoice_number))
+        }
+        _ => {}
+    }
+
+    if payment.amount <= 0 || payment.amount > outstanding {
+        return Err(anyhow::anyhow!(
+            "Payment amount must be between 1 and the outstanding {outstanding} cents"
+        ));
+    }
+
+    let date = payment
+        .date
+        .unwrap_or(chrono::Local::now().naive_local());
+
+    let mut tx = db.begin().await?;
+
+    // The outstanding amount is checked again by the update itself, so two payments at the same
+    // time can't pay more than is due. The payment that settles the invoice sets its payment date
+    let settled = sqlx::query!(
+        r#"UPDATE invoices SET
+        paid_total = paid_total + ?,
+        payment_date = CASE
+            WHEN paid_total + ? >= total_after_vat - credited_total THEN ?
+            ELSE payment_date
+        END
+        WHERE id = ?
+        AND written_off_date IS NULL
+        AND paid_total + ? <= total_after_vat - credited_total"#,
+        payment.amount,
+        payment.amount,
+        date,
+        invoice_id,
+        payment.amount
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if settled.rows_affected() == 0 {
+        return Err(anyhow::anyhow!(
+            "Invoice {} changed while paying it, its outstanding amount is less than {} cents",
+            invoice.invoice_number,
+            payment.amount
+        ));
+    }
+
+    let result = sqlx::query!(
+        r#"INSERT INTO payments (invoice_id, amount, date, method) VALUES (?, ?, ?, ?)"#,
+        invoice_id,
+        payment.amount,
+        date,
+        payment.method
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(anyhow::anyhow!("Failed to insert payment"));
+    }
+
+    tx.commit().await?;
+
+    get_payment(db, result.last_insert_rowid()).await
+}
+
+pub async fn write_off_invoice(db: &SqlitePool, id: i64) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"UPDATE invoices SET
+        written_off_date = CURRENT_TIMESTAMP
+        WHERE id = ? AND written_off_date IS NULL"#,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
 pub async fn get_schedule(db: &SqlitePool, id: i64) -> Result<Schedule> {
     sqlx::query_as!(Schedule, r#"SELECT * FROM schedule WHERE id = ?"#, id)
         .fetch_one(db)
@@ -1913,6 +5045,7 @@ pub async fn get_schedule(db: &SqlitePool, id: i64) -> Result<Schedule> {
 }
 
 pub async fn add_schedule(db: &SqlitePool, schedule: &ScheduleCreateArgs) -> Result<i64> {
+    let interval = schedule.interval.map(|interval| interval.to_string());
     let result = sqlx::query!(
         r#"
 INSERT INTO schedule (
@@ -1931,7 +5064,7 @@ INSERT INTO schedule (
         schedule.quote_id,
         schedule.query_id,
         schedule.date,
-        schedule.interval
+        interval
     )
     .execute(db)
     .await?;
@@ -1944,6 +5077,7 @@ INSERT INTO schedule (
 }
 
 pub async fn update_schedule(db: &SqlitePool, id: i64, schedule: &ScheduleUpdateArgs) -> Result<u64> {
+    let interval = schedule.interval.map(|interval| interval.to_string());
     let result = sqlx::query!(
         r#"UPDATE schedule SET
         contract_id = COALESCE(?, contract_id),
@@ -1960,7 +5094,7 @@ pub async fn update_schedule(db: &SqlitePool, id: i64, schedule: &ScheduleUpdate
         schedule.quote_id,
         schedule.query_id,
         schedule.date,
-        schedule.interval,
+        interval,
         id
     )
     .execute(db)
@@ -1972,12 +5106,228 @@ pub async fn update_schedule(db: &SqlitePool, id: i64, schedule: &ScheduleUpdate
 
     Ok(result.rows_affected())
 }
-/* */
-pub async fn create_report(_db: &SqlitePool, _report: &FinanceReportArgs) -> Result<i64> {
-    todo!()
+pub async fn get_report(db: &SqlitePool, id: i64) -> Result<FinanceReport> {
+    sqlx::query_as!(FinanceReport, r#"SELECT * FROM finance_reports WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+/// A report of the range, with a row per currency of its documents
+pub async fn create_report(
+    db: &SqlitePool,
+    report: &FinanceReportArgs,
+) -> Result<Vec<FinanceReport>> {
+    insert_report(db, report, None).await
+}
+
+async fn insert_report(
+    db: &SqlitePool,
+    report: &FinanceReportArgs,
+    query_id: Option<i64>,
+) -> Result<Vec<FinanceReport>> {
+    let to_date = report
+        .to_date
+        .unwrap_or(chrono::Local::now().naive_local());
+
+    // An account matches as either sender or recipient, a company matches through its accounts
+    let invoices = sqlx::query_as!(
+        Invoice,
+        r#"
+SELECT * FROM invoices
+WHERE ($1 IS NULL OR send_date >= $1)
+AND send_date <= $2
+AND ($3 IS NULL OR sender_id = $3 OR recipient_id = $3)
+AND ($4 IS NULL
+    OR sender_id IN (SELECT id FROM accounts WHERE company_id = $4)
+    OR recipient_id IN (SELECT id FROM accounts WHERE company_id = $4))
+"#,
+        report.from_date,
+        to_date,
+        report.account_id,
+        report.company_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let credit_notes = sqlx::query_as!(
+        CreditNote,
+        r#"
+SELECT * FROM credit_notes
+WHERE ($1 IS NULL OR send_date >= $1)
+AND send_date <= $2
+AND ($3 IS NULL OR sender_id = $3 OR recipient_id = $3)
+AND ($4 IS NULL
+    OR sender_id IN (SELECT id FROM accounts WHERE company_id = $4)
+    OR recipient_id IN (SELECT id FROM accounts WHERE company_id = $4))
+"#,
+        report.from_date,
+        to_date,
+        report.account_id,
+        report.company_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let quotes = sqlx::query_as!(
+        Quote,
+        r#"
+SELECT * FROM quotes
+WHERE ($1 IS NULL OR send_date >= $1)
+AND send_date <= $2
+AND ($3 IS NULL OR sender_id = $3 OR recipient_id = $3)
+AND ($4 IS NULL
+    OR sender_id IN (SELECT id FROM accounts WHERE company_id = $4)
+    OR recipient_id IN (SELECT id FROM accounts WHERE company_id = $4))
+"#,
+        report.from_date,
+        to_date,
+        report.account_id,
+        report.company_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    // Amounts in different currencies don't add up, the report gets a row per currency. A range
+    // without documents still gets a row in the default currency
+    let mut currencies = invoices
+        .iter()
+        .map(|invoice| invoice.currency.clone())
+        .chain(credit_notes.iter().map(|credit_note| credit_note.currency.clone()))
+        .chain(quotes.iter().map(|quote| quote.currency.clone()))
+        .collect::<Vec<_>>();
+    currencies.sort();
+    currencies.dedup();
+    if currencies.is_empty() {
+        currencies.push("EUR".to_string());
+    }
+
+    let mut tx = db.begin().await?;
+    let mut report_ids = Vec::new();
+    for currency in &currencies {
+        let mut revenue_before_vat = 0;
+        let mut discount_total = 0;
+        let mut revenue_after_vat = 0;
+        let mut paid_total = 0;
+        let mut written_off_total = 0;
+        let mut invoice_count = 0;
+        for invoice in invoices.iter().filter(|invoice| &invoice.currency == currency) {
+            invoice_count += 1;
+            revenue_before_vat += invoice.total_before_vat;
+            discount_total += invoice.discount.unwrap_or(0);
+            revenue_after_vat += invoice.total_after_vat;
+            match invoice.status() {
+                InvoiceStatus::Paid => paid_total += invoice.paid_total.max(invoice.amount_due()),
+                InvoiceStatus::WrittenOff => {
+                    paid_total += invoice.paid_total;
+                    written_off_total += invoice.amount_due() - invoice.paid_total;
+                }
+                _ => paid_total += invoice.paid_total,
+            }
+        }
+        // Credit notes are negative, so they net against the revenue of the invoices they reverse.
+        // Their totals are after discount, the share of the discount they reverse is added back to
+        // compare with the revenue before discount
+        for credit_note in credit_notes.iter().filter(|credit_note| &credit_note.currency == currency) {
+            revenue_before_vat += credit_note.total_before_vat + credit_note.discount;
+            discount_total += credit_note.discount;
+            revenue_after_vat += credit_note.total_after_vat;
+        }
+        let vat_collected = revenue_after_vat - (revenue_before_vat - discount_total);
+        let outstanding_total = revenue_after_vat - paid_total - written_off_total;
+        let currency_quotes = quotes
+            .iter()
+            .filter(|quote| &quote.currency == currency)
+            .collect::<Vec<_>>();
+        let quoted_before_vat = currency_quotes.iter().fold(0, |acc, quote| acc + quote.total_before_vat);
+        let quoted_after_vat = currency_quotes.iter().fold(0, |acc, quote| acc + quote.total_after_vat);
+        let quote_count = currency_quotes.len() as i64;
+
+        let result = sqlx::query!(
+            r#"
+INSERT INTO finance_reports (
+    account_id,
+    company_id,
+    query_id,
+    from_date,
+    to_date,
+    currency,
+    invoice_count,
+    quote_count,
+    revenue_before_vat,
+    discount_total,
+    vat_collected,
+    revenue_after_vat,
+    paid_total,
+    outstanding_total,
+    quoted_before_vat,
+    quoted_after_vat
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#,
+            report.account_id,
+            report.company_id,
+            query_id,
+            report.from_date,
+            to_date,
+            currency,
+            invoice_count,
+            quote_count,
+            revenue_before_vat,
+            discount_total,
+            vat_collected,
+            revenue_after_vat,
+            paid_total,
+            outstanding_total,
+            quoted_before_vat,
+            quoted_after_vat
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Failed to insert report"));
+        }
+
+        report_ids.push(result.last_insert_rowid());
+    }
+    tx.commit().await?;
+
+    let mut reports = Vec::new();
+    for id in report_ids {
+        reports.push(get_report(db, id).await?);
+    }
+
+    Ok(reports)
+}
+
+pub async fn get_query(db: &SqlitePool, id: i64) -> Result<FinanceQuery> {
+    sqlx::query_as!(FinanceQuery, r#"SELECT * FROM finance_queries WHERE id = ?"#, id)
+        .fetch_one(db)
+        .await
+        .map_err(anyhow::Error::msg)
+}
+
+pub async fn run_query(db: &SqlitePool, id: i64) -> Result<Vec<FinanceReport>> {
+    let query = get_query(db, id).await?;
+    let range = query
+        .range
+        .ok_or(anyhow::anyhow!("Query {id} has no range"))?
+        .parse::<Interval>()?;
+    let to_date = chrono::Local::now().naive_local();
+    let from_date = range.sub_from(to_date)?;
+
+    let report = FinanceReportArgs {
+        account_id: query.account_id,
+        company_id: query.company_id,
+        from_date: Some(from_date),
+        to_date: Some(to_date),
+    };
+
+    insert_report(db, &report, Some(query.id)).await
 }
 
-pub async fn add_query(db: &SqlitePool, query: &FinanceCreateQueryArgs) -> Result<u64> {
+pub async fn add_query(db: &SqlitePool, query: &FinanceCreateQueryArgs) -> Result<i64> {
+    let range = query.range.map(|range| range.to_string());
     let result = sqlx::query!(
         r#"
 INSERT INTO finance_queries (
@@ -1988,19 +5338,20 @@ INSERT INTO finance_queries (
 "#,
         query.account_id,
         query.company_id,
-        query.range
+        range
     )
     .execute(db)
     .await?;
 
     if result.rows_affected() == 0 {
-        return Err(anyhow::anyhow!("Failed to update query"));
+        return Err(anyhow::anyhow!("Failed to insert query"));
     }
 
-    Ok(result.rows_affected())
+    Ok(result.last_insert_rowid())
 }
 
 pub async fn update_query(db: &SqlitePool, id: i64, query: &FinanceUpdateQueryArgs) -> Result<u64> {
+    let range = query.range.map(|range| range.to_string());
     let result = sqlx::query!(
         r#"UPDATE finance_queries SET
         account_id = COALESCE(?, account_id),
@@ -2009,7 +5360,7 @@ pub async fn update_query(db: &SqlitePool, id: i64, query: &FinanceUpdateQueryAr
         WHERE id = ?"#,
         query.account_id,
         query.company_id,
-        query.range,
+        range,
         id
     )
     .execute(db)
@@ -2020,4 +5371,78 @@ pub async fn update_query(db: &SqlitePool, id: i64, query: &FinanceUpdateQueryAr
     }
 
     Ok(result.rows_affected())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn formats_invoice_numbers() {
+        let date = date(2025, 3, 7);
+
+        assert_eq!(format_invoice_number("{YYYY}{seq:05}", 42, date).unwrap(), "202500042");
+        assert_eq!(format_invoice_number("INV-{YY}{MM}-{seq}", 42, date).unwrap(), "INV-2503-42");
+        assert_eq!(format_invoice_number("{seq:03}", 1234, date).unwrap(), "1234");
+    }
+
+    #[test]
+    fn rejects_invalid_invoice_number_formats() {
+        let date = date(2025, 3, 7);
+
+        for format in ["{YYYY}", "{YYYY}{seq", "{seq:x}", "{DD}{seq}"] {
+            assert!(
+                format_invoice_number(format, 1, date).is_err(),
+                "{format} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn counts_periods_from_the_start_across_month_ends() {
+        let periods = contract_periods(date(2025, 1, 31), 1, date(2025, 3, 31), None).unwrap();
+
+        assert_eq!(
+            periods,
+            vec![
+                (date(2025, 1, 31), date(2025, 2, 28), 1.0),
+                (date(2025, 2, 28), date(2025, 3, 31), 1.0),
+                (date(2025, 3, 31), date(2025, 4, 30), 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn pro_rates_the_period_the_contract_ends_in() {
+        let periods =
+            contract_periods(date(2025, 1, 1), 3, date(2025, 6, 1), Some(date(2025, 5, 16))).unwrap();
+
+        assert_eq!(
+            periods,
+            vec![
+                (date(2025, 1, 1), date(2025, 4, 1), 3.0),
+                (date(2025, 4, 1), date(2025, 5, 16), 1.48),
+            ]
+        );
+
+        let periods =
+            contract_periods(date(2025, 1, 15), 1, date(2025, 3, 1), Some(date(2025, 2, 1))).unwrap();
+        assert_eq!(periods, vec![(date(2025, 1, 15), date(2025, 2, 1), 0.55)]);
+    }
+
+    #[test]
+    fn has_no_periods_before_the_start() {
+        let periods = contract_periods(date(2025, 2, 1), 1, date(2025, 1, 31), None).unwrap();
+
+        assert!(periods.is_empty());
+    }
+}
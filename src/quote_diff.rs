@@ -0,0 +1,227 @@
+use serde::Serialize;
+
+use crate::models::{Quote, QuoteLine};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineChange {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A line that differs between two revisions, amounts are in cents and `None` on the side
+/// the line is missing from
+#[derive(Debug, Serialize)]
+pub struct QuoteLineDiff {
+    pub change: LineChange,
+    pub title: String,
+    pub old_quantity: Option<f64>,
+    pub new_quantity: Option<f64>,
+    pub old_unit_price: Option<i64>,
+    pub new_unit_price: Option<i64>,
+    pub old_total: Option<i64>,
+    pub new_total: Option<i64>,
+    pub old_vat_percentage: Option<i64>,
+    pub new_vat_percentage: Option<i64>,
+}
+
+/// The changed lines and totals from one quote revision to another
+#[derive(Debug, Serialize)]
+pub struct QuoteDiff {
+    pub old_quote_id: i64,
+    pub old_revision: i64,
+    pub new_quote_id: i64,
+    pub new_revision: i64,
+    pub currency: String,
+    pub lines: Vec<QuoteLineDiff>,
+    pub old_total_before_vat: i64,
+    pub new_total_before_vat: i64,
+    pub old_discount: i64,
+    pub new_discount: i64,
+    pub old_total_after_vat: i64,
+    pub new_total_after_vat: i64,
+}
+
+impl QuoteDiff {
+    /// Lines of the same task are compared, lines without a task are matched on their title
+    pub fn new(old: &Quote, old_lines: &[QuoteLine], new: &Quote, new_lines: &[QuoteLine]) -> Self {
+        let same_line = |a: &QuoteLine, b: &QuoteLine| match (a.task_id, b.task_id) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => a.title == b.title,
+            _ => false,
+        };
+
+        let mut matched = vec![false; new_lines.len()];
+        let mut lines = Vec::new();
+
+        for old_line in old_lines {
+            let new_line = new_lines
+                .iter()
+                .enumerate()
+                .find(|(i, new_line)| !matched[*i] && same_line(old_line, new_line));
+
+            match new_line {
+                Some((i, new_line)) => {
+                    matched[i] = true;
+
+                    if old_line.quantity != new_line.quantity
+                        || old_line.unit_price != new_line.unit_price
+                        || old_line.total != new_line.total
+                        || old_line.vat_percentage != new_line.vat_percentage
+                        || old_line.title != new_line.title
+                    {
+                        lines.push(line_diff(LineChange::Changed, Some(old_line), Some(new_line)));
+                    }
+                }
+                None => lines.push(line_diff(LineChange::Removed, Some(old_line), None)),
+            }
+        }
+
+        for (new_line, _) in new_lines.iter().zip(matched).filter(|(_, matched)| !matched) {
+            lines.push(line_diff(LineChange::Added, None, Some(new_line)));
+        }
+
+        Self {
+            old_quote_id: old.id,
+            old_revision: old.revision,
+            new_quote_id: new.id,
+            new_revision: new.revision,
+            currency: new.currency.clone(),
+            lines,
+            old_total_before_vat: old.total_before_vat,
+            new_total_before_vat: new.total_before_vat,
+            old_discount: old.discount.unwrap_or(0),
+            new_discount: new.discount.unwrap_or(0),
+            old_total_after_vat: old.total_after_vat,
+            new_total_after_vat: new.total_after_vat,
+        }
+    }
+}
+
+fn line_diff(change: LineChange, old: Option<&QuoteLine>, new: Option<&QuoteLine>) -> QuoteLineDiff {
+    QuoteLineDiff {
+        change,
+        title: new.or(old).map_or("".to_string(), |line| line.title.clone()),
+        old_quantity: old.map(|line| line.quantity),
+        new_quantity: new.map(|line| line.quantity),
+        old_unit_price: old.map(|line| line.unit_price),
+        new_unit_price: new.map(|line| line.unit_price),
+        old_total: old.map(|line| line.total),
+        new_total: new.map(|line| line.total),
+        old_vat_percentage: old.map(|line| line.vat_percentage),
+        new_vat_percentage: new.map(|line| line.vat_percentage),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::money::Money;
+
+    fn quote(id: i64, revision: i64, total_before_vat: i64) -> Quote {
+        Quote {
+            id,
+            sender_id: 1,
+            recipient_id: 2,
+            send_date: None,
+            expire_date: None,
+            project_duration: None,
+            project_id: None,
+            remarks: None,
+            total_before_vat,
+            discount: None,
+            vat_percentage: Some(21),
+            currency: "EUR".to_string(),
+            total_after_vat: Money::new(total_before_vat, "EUR").with_vat(21).amount,
+            quote_url: None,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            reverse_charge: false,
+            accepted_date: None,
+            accepted_by: None,
+            rejected_date: None,
+            revision,
+            superseded_by: None,
+        }
+    }
+
+    fn line(task_id: Option<i64>, title: &str, quantity: f64, unit_price: i64) -> QuoteLine {
+        QuoteLine {
+            id: 0,
+            quote_id: 0,
+            position: 0,
+            title: title.to_string(),
+            description: None,
+            quantity,
+            unit: "hours".to_string(),
+            unit_price,
+            total: (quantity * unit_price as f64).round() as i64,
+            vat_percentage: 21,
+            task_id,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            milestone: None,
+            rate_card: None,
+        }
+    }
+
+    #[test]
+    fn lists_added_removed_and_changed_lines() {
+        let old_lines = [
+            line(Some(1), "Design", 2.0, 6000),
+            line(Some(2), "Build", 10.0, 6000),
+            line(None, "Hosting", 1.0, 2500),
+        ];
+        let new_lines = [
+            line(Some(1), "Design", 2.0, 6000),
+            line(Some(2), "Build", 12.5, 6000),
+            line(None, "Support", 1.0, 5000),
+        ];
+        let diff = QuoteDiff::new(&quote(1, 1, 74500), &old_lines, &quote(2, 2, 92000), &new_lines);
+
+        let changes = diff
+            .lines
+            .iter()
+            .map(|line| (line.change, line.title.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (LineChange::Changed, "Build"),
+                (LineChange::Removed, "Hosting"),
+                (LineChange::Added, "Support"),
+            ]
+        );
+        assert_eq!(diff.lines[0].old_total, Some(60000));
+        assert_eq!(diff.lines[0].new_total, Some(75000));
+        assert_eq!(diff.lines[1].new_total, None);
+        assert_eq!(diff.lines[2].old_total, None);
+        assert_eq!((diff.old_revision, diff.new_revision), (1, 2));
+        assert_eq!((diff.old_total_after_vat, diff.new_total_after_vat), (90145, 111320));
+    }
+
+    #[test]
+    fn matches_lines_of_a_task_after_it_is_renamed() {
+        let diff = QuoteDiff::new(
+            &quote(1, 1, 6000),
+            &[line(Some(1), "Design", 1.0, 6000)],
+            &quote(2, 2, 6000),
+            &[line(Some(1), "Visual design", 1.0, 6000)],
+        );
+
+        assert_eq!(diff.lines.len(), 1);
+        assert_eq!(diff.lines[0].change, LineChange::Changed);
+        assert_eq!(diff.lines[0].title, "Visual design");
+    }
+
+    #[test]
+    fn has_no_lines_for_an_unchanged_quote() {
+        let lines = [line(Some(1), "Design", 1.0, 6000), line(None, "Hosting", 1.0, 2500)];
+        let diff = QuoteDiff::new(&quote(1, 1, 8500), &lines, &quote(2, 2, 8500), &lines);
+
+        assert!(diff.lines.is_empty());
+    }
+}
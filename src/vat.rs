@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::money::Money;
+
+/// The VAT note printed on reverse charged documents, followed by the VAT number of the recipient
+pub const REVERSE_CHARGE_NOTE: &str =
+    "BTW verlegd / VAT reverse charged (art. 196 Directive 2006/112/EC), VAT number recipient:";
+
+/// The amount before VAT taxed at one rate, after its share of the discount
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VatLine {
+    pub vat_percentage: i64,
+    pub base: Money,
+    pub vat: Money,
+}
+
+/// The VAT of a document per rate, so every rate is rounded once
+#[derive(Debug, Clone, Serialize)]
+pub struct VatBreakdown {
+    pub currency: String,
+    pub reverse_charge: bool,
+    pub lines: Vec<VatLine>,
+}
+
+impl VatBreakdown {
+    /// Group `(amount, vat_percentage)` lines per rate and spread the discount over the rates in
+    /// proportion to their amount. With reverse charge every line is taxed at 0%
+    pub fn new(lines: &[(i64, i64)], discount: i64, currency: &str, reverse_charge: bool) -> Self {
+        let mut per_rate: BTreeMap<i64, i64> = BTreeMap::new();
+        for (amount, vat_percentage) in lines {
+            let vat_percentage = if reverse_charge { 0 } else { *vat_percentage };
+            *per_rate.entry(vat_percentage).or_insert(0) += amount;
+        }
+
+        let total: i64 = per_rate.values().sum();
+        let mut discount_left = discount;
+        let rate_count = per_rate.len();
+        let lines = per_rate
+            .into_iter()
+            .enumerate()
+            .map(|(i, (vat_percentage, amount))| {
+                // The last rate takes what is left, so the shares add up to the discount
+                let share = if i + 1 == rate_count || total == 0 {
+                    discount_left
+                } else {
+                    (discount as i128 * amount as i128 / total as i128) as i64
+                };
+                discount_left -= share;
+
+                let base = Money::new(amount - share, currency);
+                VatLine {
+                    vat_percentage,
+                    vat: base.vat(vat_percentage),
+                    base,
+                }
+            })
+            .collect();
+
+        Self {
+            currency: currency.to_string(),
+            reverse_charge,
+            lines,
+        }
+    }
+
+    pub fn total_before_vat(&self) -> Money {
+        self.lines
+            .iter()
+            .fold(Money::zero(&self.currency), |acc, line| acc + line.base.clone())
+    }
+
+    pub fn vat(&self) -> Money {
+        self.lines
+            .iter()
+            .fold(Money::zero(&self.currency), |acc, line| acc + line.vat.clone())
+    }
+
+    pub fn total_after_vat(&self) -> Money {
+        self.total_before_vat() + self.vat()
+    }
+
+    /// The rate of the whole document, or `None` when it mixes rates
+    pub fn vat_percentage(&self) -> Option<i64> {
+        match self.lines.as_slice() {
+            [line] => Some(line.vat_percentage),
+            [] if self.reverse_charge => Some(0),
+            _ => None,
+        }
+    }
+}
+
+/// Reverse charge applies when the recipient has a VAT number of another country than the sender
+pub fn is_reverse_charge(sender_vat_number: Option<&str>, recipient_vat_number: Option<&str>) -> bool {
+    let country = |vat_number: &str| {
+        let vat_number = vat_number.trim().to_uppercase();
+        vat_number
+            .get(..2)
+            .filter(|prefix| prefix.chars().all(|c| c.is_ascii_alphabetic()))
+            .map(str::to_string)
+    };
+
+    match (sender_vat_number.and_then(country), recipient_vat_number.and_then(country)) {
+        (Some(sender), Some(recipient)) => sender != recipient,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eur(amount: i64) -> Money {
+        Money::new(amount, "EUR")
+    }
+
+    #[test]
+    fn spreads_the_discount_over_the_rates() {
+        let breakdown = VatBreakdown::new(&[(10000, 21), (5000, 9)], 1000, "EUR", false);
+
+        assert_eq!(
+            breakdown.lines,
+            vec![
+                VatLine { vat_percentage: 9, base: eur(4667), vat: eur(420) },
+                VatLine { vat_percentage: 21, base: eur(9333), vat: eur(1960) },
+            ]
+        );
+        assert_eq!(breakdown.total_before_vat(), eur(14000));
+        assert_eq!(breakdown.vat(), eur(2380));
+        assert_eq!(breakdown.total_after_vat(), eur(16380));
+        assert_eq!(breakdown.vat_percentage(), None);
+    }
+
+    #[test]
+    fn groups_lines_of_the_same_rate() {
+        let breakdown = VatBreakdown::new(&[(1000, 21), (2000, 21)], 0, "EUR", false);
+
+        assert_eq!(
+            breakdown.lines,
+            vec![VatLine { vat_percentage: 21, base: eur(3000), vat: eur(630) }]
+        );
+        assert_eq!(breakdown.vat_percentage(), Some(21));
+    }
+
+    #[test]
+    fn taxes_reverse_charged_lines_at_zero() {
+        let breakdown = VatBreakdown::new(&[(10000, 21), (5000, 9)], 500, "EUR", true);
+
+        assert_eq!(
+            breakdown.lines,
+            vec![VatLine { vat_percentage: 0, base: eur(14500), vat: eur(0) }]
+        );
+        assert_eq!(breakdown.total_after_vat(), eur(14500));
+        assert_eq!(breakdown.vat_percentage(), Some(0));
+        assert_eq!(VatBreakdown::new(&[], 0, "EUR", true).vat_percentage(), Some(0));
+    }
+
+    #[test]
+    fn reverse_charges_between_countries() {
+        assert!(is_reverse_charge(Some("NL123456789B01"), Some("be0123456789")));
+        assert!(!is_reverse_charge(Some("NL123456789B01"), Some("NL987654321B01")));
+        assert!(!is_reverse_charge(Some("NL123456789B01"), None));
+        assert!(!is_reverse_charge(Some("NL123456789B01"), Some("123456789")));
+    }
+}